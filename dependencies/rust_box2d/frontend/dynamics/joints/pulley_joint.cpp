@@ -52,6 +52,12 @@ b2Vec2 PulleyJoint_get_ground_anchor_a(const b2PulleyJoint* self) {
 b2Vec2 PulleyJoint_get_ground_anchor_b(const b2PulleyJoint* self) {
     return self->GetGroundAnchorB();
 }
+const b2Vec2* PulleyJoint_get_local_anchor_a(const b2PulleyJoint* self) {
+    return &self->GetLocalAnchorA();
+}
+const b2Vec2* PulleyJoint_get_local_anchor_b(const b2PulleyJoint* self) {
+    return &self->GetLocalAnchorB();
+}
 f32 PulleyJoint_get_length_a(const b2PulleyJoint* self) {
     return self->GetLengthA();
 }
@@ -153,12 +153,12 @@ pub trait Joint: WrappedBase<ffi::Joint> + FromFFI<ffi::Joint> {
         unsafe { ffi::Joint_get_anchor_b_virtual(self.base_ptr()) }
     }
 
-    fn reaction_force(&self) -> Vec2 {
-        unsafe { ffi::Joint_get_reaction_force_virtual(self.base_ptr()) }
+    fn reaction_force(&self, inv_dt: f32) -> Vec2 {
+        unsafe { ffi::Joint_get_reaction_force_virtual(self.base_ptr(), inv_dt) }
     }
 
-    fn reaction_torque(&self) -> f32 {
-        unsafe { ffi::Joint_get_reaction_torque_virtual(self.base_ptr()) }
+    fn reaction_torque(&self, inv_dt: f32) -> f32 {
+        unsafe { ffi::Joint_get_reaction_torque_virtual(self.base_ptr(), inv_dt) }
     }
 
     fn is_active(&self) -> bool {
@@ -284,8 +284,8 @@ pub mod ffi {
         pub fn Joint_get_body_b(slf: *mut Joint) -> *mut Body;
         pub fn Joint_get_anchor_a_virtual(slf: *const Joint) -> Vec2;
         pub fn Joint_get_anchor_b_virtual(slf: *const Joint) -> Vec2;
-        pub fn Joint_get_reaction_force_virtual(slf: *const Joint) -> Vec2;
-        pub fn Joint_get_reaction_torque_virtual(slf: *const Joint) -> f32;
+        pub fn Joint_get_reaction_force_virtual(slf: *const Joint, inv_dt: f32) -> Vec2;
+        pub fn Joint_get_reaction_torque_virtual(slf: *const Joint, inv_dt: f32) -> f32;
         // pub fn Joint_get_next(slf: *mut Joint) -> *mut Joint;
         // pub fn Joint_get_next_const(slf: *const Joint) -> *const Joint;
         pub fn Joint_is_active(slf: *const Joint) -> bool;
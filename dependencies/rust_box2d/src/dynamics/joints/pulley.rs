@@ -89,6 +89,18 @@ impl PulleyJoint {
         unsafe { ffi::PulleyJoint_get_ground_anchor_b(self.ptr()) }
     }
 
+    pub fn local_anchor_a<'a>(&'a self) -> &'a Vec2 {
+        unsafe {
+            &*ffi::PulleyJoint_get_local_anchor_a(self.ptr()) // Comes from a C++ &
+        }
+    }
+
+    pub fn local_anchor_b<'a>(&'a self) -> &'a Vec2 {
+        unsafe {
+            &*ffi::PulleyJoint_get_local_anchor_b(self.ptr()) // Comes from a C++ &
+        }
+    }
+
     pub fn length_a(&self) -> f32 {
         unsafe { ffi::PulleyJoint_get_length_a(self.ptr()) }
     }
@@ -144,6 +156,8 @@ pub mod ffi {
         pub fn Joint_as_pulley_joint(slf: *mut Joint) -> *mut PulleyJoint;
         pub fn PulleyJoint_get_ground_anchor_a(slf: *const PulleyJoint) -> Vec2;
         pub fn PulleyJoint_get_ground_anchor_b(slf: *const PulleyJoint) -> Vec2;
+        pub fn PulleyJoint_get_local_anchor_a(slf: *const PulleyJoint) -> *const Vec2;
+        pub fn PulleyJoint_get_local_anchor_b(slf: *const PulleyJoint) -> *const Vec2;
         pub fn PulleyJoint_get_length_a(slf: *const PulleyJoint) -> f32;
         pub fn PulleyJoint_get_length_b(slf: *const PulleyJoint) -> f32;
         pub fn PulleyJoint_get_ratio(slf: *const PulleyJoint) -> f32;
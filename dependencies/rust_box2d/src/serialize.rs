@@ -724,8 +724,6 @@ snapshot! {
 
 impl PulleyJointSnapshot {
     pub fn take(joint: &PulleyJoint) -> Self {
-        /*
-        TODO
         PulleyJointSnapshot {
             body_a: BodyId(joint.body_a().index()),
             body_b: BodyId(joint.body_b().index()),
@@ -738,8 +736,6 @@ impl PulleyJointSnapshot {
             length_b: joint.length_b(),
             ratio: joint.ratio(),
         }
-        */
-        unimplemented!()
     }
 
     pub fn rebuild<U: UserDataTypes>(&self,
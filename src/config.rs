@@ -1,6 +1,11 @@
 extern crate toml;
 extern crate serde;
 
+use game::bot::Difficulty;
+use input::OpposingKeys;
+use input::ResponseCurve;
+
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::prelude::*;
 
@@ -8,6 +13,11 @@ use std::io::prelude::*;
 pub struct Config {
     pub video: VideoConfig,
     pub input: InputConfig,
+    pub session: SessionConfig,
+    pub simulation: SimulationConfig,
+    pub bots: BotConfig,
+    #[serde(default)]
+    pub avoid_the_wall: AvoidTheWallConfig,
 }
 
 #[derive(Debug, Deserialize)]
@@ -46,8 +56,94 @@ impl VideoConfig {
 
 #[derive(Debug, Deserialize)]
 pub struct InputConfig {
-    pub deadzone: u16,
-    pub keyboard: bool,
+    // Stick and trigger response, fractions of full deflection
+    pub deadzone: f32,
+    pub outer_deadzone: f32,
+    pub trigger_threshold: f32,
+    pub curve: ResponseCurve,
+    #[serde(default)]
+    pub curve_points: Vec<f32>,
+    // Per controller overrides of the above
+    #[serde(default)]
+    pub controllers: Vec<ControllerConfig>,
+    // Keyboard players, player n uses keymaps[n]
+    pub keyboard_players: u32,
+    pub keymaps: Vec<KeyMapConfig>,
+    pub opposing_keys: OpposingKeys,
+    // Seconds for a keyboard stick to go from center to full, 0 for instant
+    pub keyboard_ramp: f32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ControllerConfig {
+    pub guid: String,
+    pub deadzone: Option<f32>,
+    pub outer_deadzone: Option<f32>,
+    pub trigger_threshold: Option<f32>,
+    pub curve: Option<ResponseCurve>,
+    pub curve_points: Option<Vec<f32>>,
+}
+
+// Keys by SDL key name, e.g. "W", "Up", "Keypad 8" or "Left Shift"
+#[derive(Debug, Deserialize)]
+pub struct KeyMapConfig {
+    pub up: String,
+    pub down: String,
+    pub left: String,
+    pub right: String,
+    // SDL controller button name (a, b, x, y, start, leftshoulder, ...) to key name
+    #[serde(default)]
+    pub buttons: HashMap<String, String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SessionConfig {
+    pub minigames: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SimulationConfig {
+    pub tick_rate: u32,
+    pub max_steps_per_frame: u32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BotConfig {
+    pub count: u32,
+    pub difficulty: Difficulty,
+}
+
+// Difficulty ramp of avoid the wall, every wall is faster, sooner and narrower than the one
+// before until the limits. Seconds and world units.
+#[derive(Debug, Clone, Deserialize)]
+pub struct AvoidTheWallConfig {
+    pub start_speed: f32,
+    pub speed_step: f32,
+    pub max_speed: f32,
+    // Time between two walls
+    pub start_interval: f32,
+    pub interval_step: f32,
+    pub min_interval: f32,
+    // Width of the gap in each wall
+    pub start_gap: f32,
+    pub gap_step: f32,
+    pub min_gap: f32,
+}
+
+impl Default for AvoidTheWallConfig {
+    fn default() -> AvoidTheWallConfig {
+        AvoidTheWallConfig {
+            start_speed: 0.3,
+            speed_step: 0.02,
+            max_speed: 1.0,
+            start_interval: 3.0,
+            interval_step: 0.1,
+            min_interval: 1.2,
+            start_gap: 0.5,
+            gap_step: 0.01,
+            min_gap: 0.2,
+        }
+    }
 }
 
 const SETTINGS_FILE : &str = "settings.toml";
@@ -29,6 +29,9 @@ use stl::Triangle;
 use game::minigame::Point as WorldPoint;
 use config::VideoConfig;
 use physics::B2Point;
+use physics::ShapeSpec;
+
+use std::f32::consts::PI;
 
 pub type ColorFormat = gfx::format::Rgba8;
 pub type DepthFormat = gfx::format::DepthStencil;
@@ -84,6 +87,13 @@ pub type Color = [f32; 3];
 
 const CLEAR_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
 
+// Segments of a circle with radius 1, smaller circles get proportionally fewer
+const CIRCLE_SEGMENTS: f32 = 128.0;
+const MIN_CIRCLE_SEGMENTS: u32 = 12;
+
+// Width of edges and chains drawn from shape specs
+const LINE_WIDTH: f32 = 0.01;
+
 // Identity matrix
 pub const IDENTITY: Transform = Transform {
     transform: [[1.0, 0.0, 0.0, 0.0],
@@ -95,6 +105,15 @@ pub const IDENTITY: Transform = Transform {
 pub trait DrawComponent {
     fn set_color(&mut self, new_color: Color);
     fn draw(&mut self, resize: bool, encoder : &mut Encoder<Resources, CommandBuffer>, color_view: &RenderTargetView<Resources, ColorFormat>);
+    // Description of what draw() would submit, used when there is no GPU
+    fn draw_call(&self) -> DrawCall;
+}
+
+// A draw recorded by the headless backend
+#[derive(Debug, Clone)]
+pub enum DrawCall {
+    Vertices { vertex_count: usize, color: Option<Color>, transform: [[f32; 4]; 4] },
+    Text { text: String, color: Color, transform: [[f32; 4]; 4] },
 }
 
 pub struct VertexComponent {
@@ -102,7 +121,8 @@ pub struct VertexComponent {
     translation: [f32; 2],
     rotation: f32,
     pub transform: Transform,
-    bundle: Bundle<Resources, pipe::Data<Resources>>,
+    // None when created by a headless DrawSystem
+    bundle: Option<Bundle<Resources, pipe::Data<Resources>>>,
     update_model: bool,
 }
 
@@ -116,20 +136,39 @@ impl DrawComponent for VertexComponent {
     }
 
     fn draw(&mut self, resize : bool, encoder: &mut Encoder<Resources, CommandBuffer>, color_view: &RenderTargetView<Resources, ColorFormat>) {
+        let bundle = match self.bundle {
+            Some(ref mut bundle) => bundle,
+            None => return,
+        };
+
         if resize {
-            self.bundle.data.out = color_view.clone();
+            bundle.data.out = color_view.clone();
         }
 
-        encoder.update_constant_buffer(&self.bundle.data.transform, &self.transform);
+        encoder.update_constant_buffer(&bundle.data.transform, &self.transform);
 
         if self.update_model {
             encoder
-                .update_buffer(&self.bundle.data.vbuf, &self.vertices.clone()[..], 0)
+                .update_buffer(&bundle.data.vbuf, &self.vertices.clone()[..], 0)
                 .expect("Failed to update vertex buffer");
             self.update_model = false;
         }
 
-        self.bundle.encode(encoder)
+        bundle.encode(encoder)
+    }
+
+    fn draw_call(&self) -> DrawCall {
+        // Only report a color when the whole component is a single color
+        let color = match self.vertices.first() {
+            Some(first) if self.vertices.iter().all(|v| v.color == first.color) => Some(first.color),
+            _ => None,
+        };
+
+        return DrawCall::Vertices {
+            vertex_count: self.vertices.len(),
+            color: color,
+            transform: self.transform.transform,
+        };
     }
 }
 
@@ -138,7 +177,8 @@ pub struct TextComponent {
     pub color: Color,
     pub text: String,
     pub transform: Transform,
-    renderer: Renderer<Resources, SDLFactory>,
+    // None when created by a headless DrawSystem
+    renderer: Option<Renderer<Resources, SDLFactory>>,
 }
 
 impl DrawComponent for TextComponent {
@@ -146,15 +186,25 @@ impl DrawComponent for TextComponent {
         self.color = new_color;
     }
     fn draw(&mut self, resize: bool, encoder: &mut Encoder<Resources, CommandBuffer>, color_view: &RenderTargetView<Resources, ColorFormat>) {
-        self.renderer.add_at(
-            self.text.as_ref(),
-            [-self.transform.transform[3][0], -self.transform.transform[3][1], 1.0],
-            [self.color[0], self.color[1], self.color[2], 1.0]);
-        self.renderer.draw_at(encoder, color_view, IDENTITY.transform).unwrap();
+        if let Some(ref mut renderer) = self.renderer {
+            renderer.add_at(
+                self.text.as_ref(),
+                [-self.transform.transform[3][0], -self.transform.transform[3][1], 1.0],
+                [self.color[0], self.color[1], self.color[2], 1.0]);
+            renderer.draw_at(encoder, color_view, IDENTITY.transform).unwrap();
+        }
+    }
+
+    fn draw_call(&self) -> DrawCall {
+        return DrawCall::Text {
+            text: self.text.clone(),
+            color: self.color,
+            transform: self.transform.transform,
+        };
     }
 }
 
-pub struct DrawSystem {
+struct GLBackend {
     window: Window,
     glcontext: GLContext,
     device: GLDevice,
@@ -162,6 +212,21 @@ pub struct DrawSystem {
     color_view: RenderTargetView<Resources, ColorFormat>,
     depth_view: DepthStencilView<Resources, DepthFormat>,
     encoder: Encoder<Resources, CommandBuffer>,
+}
+
+// Records draw calls instead of rendering them, needs neither a window nor a GL context
+struct HeadlessBackend {
+    frames: u64,
+    draw_calls: Vec<DrawCall>,
+}
+
+enum Backend {
+    GL(GLBackend),
+    Headless(HeadlessBackend),
+}
+
+pub struct DrawSystem {
+    backend: Backend,
     resize: bool,
 }
 
@@ -207,19 +272,56 @@ impl DrawSystem {
         let encoder: gfx::Encoder<_, _> = factory.create_command_buffer().into();
 
         DrawSystem {
-            window: window,
-            glcontext: glcontext,
-            device: device,
-            factory: factory,
-            color_view: color_view,
-            depth_view: depth_view,
-            encoder: encoder,
+            backend: Backend::GL(GLBackend {
+                window: window,
+                glcontext: glcontext,
+                device: device,
+                factory: factory,
+                color_view: color_view,
+                depth_view: depth_view,
+                encoder: encoder,
+            }),
             resize: true,
         }
     }
 
+    pub fn new_headless() -> DrawSystem {
+        info!("Using headless draw system");
+
+        DrawSystem {
+            backend: Backend::Headless(HeadlessBackend {
+                frames: 0,
+                draw_calls: vec![],
+            }),
+            resize: false,
+        }
+    }
+
+    pub fn is_headless(&self) -> bool {
+        match self.backend {
+            Backend::Headless(_) => true,
+            Backend::GL(_) => false,
+        }
+    }
+
+    // Draw calls of the frame being recorded (or the last finished one), empty unless headless
+    pub fn draw_calls(&self) -> &[DrawCall] {
+        match self.backend {
+            Backend::Headless(ref headless) => &headless.draw_calls,
+            Backend::GL(_) => &[],
+        }
+    }
+
+    // Frames rendered so far, only counted when headless
+    pub fn frame_count(&self) -> u64 {
+        match self.backend {
+            Backend::Headless(ref headless) => headless.frames,
+            Backend::GL(_) => 0,
+        }
+    }
+
     pub fn new_vertex_component(vertices: Vec<Point>,
-                                bundle: Bundle<Resources, pipe::Data<Resources>>)
+                                bundle: Option<Bundle<Resources, pipe::Data<Resources>>>)
                                 -> VertexComponent {
         VertexComponent {
             vertices: vertices,
@@ -232,43 +334,43 @@ impl DrawSystem {
     }
 
     pub fn create_draw_object(&mut self, vertices: Vec<Vertex>) -> VertexComponent {
-        let pso = self.factory
-            .create_pipeline_simple(include_bytes!("shader/triangle_150.glslv"),
-                                    include_bytes!("shader/triangle_150.glslf"),
-                                    pipe::new())
-            .unwrap();
-
-        let vertex_count = vertices.len();
-
-        let vertex_buffer = self.factory
-            .create_buffer(vertex_count,
-                           gfx::buffer::Role::Vertex,
-                           gfx::memory::Usage::Dynamic,
-                           gfx::Bind::empty())
-            .unwrap();
+        let bundle = match self.backend {
+            Backend::GL(ref mut gl) => Some(gl.create_bundle(vertices.len())),
+            Backend::Headless(_) => None,
+        };
 
+        return DrawSystem::new_vertex_component(vertices, bundle);
+    }
 
-        let slice = Slice::new_match_vertex_buffer(&vertex_buffer);
+    // Filled circle matching a physics circle of the same radius
+    pub fn create_circle(&mut self, center: &WorldPoint, radius: f32, color: Color) -> VertexComponent {
+        let vertices = circle_vertices(center, radius, color);
+        return self.create_draw_object(vertices);
+    }
 
-        let transform_buffer = self.factory.create_constant_buffer(1);
+    pub fn create_ring(&mut self, inner_radius: f32, outer_radius: f32, color: Color) -> VertexComponent {
+        let vertices = ring_vertices(inner_radius, outer_radius, color);
+        return self.create_draw_object(vertices);
+    }
 
+    // Line through the points of an edge or chain, width in world units
+    pub fn create_line(&mut self, points: &Vec<WorldPoint>, looped: bool, width: f32, color: Color) -> VertexComponent {
+        let vertices = line_vertices(points, looped, width, color);
+        return self.create_draw_object(vertices);
+    }
 
-        let data = pipe::Data {
-            vbuf: vertex_buffer,
-            transform: transform_buffer,
-            out: self.color_view.clone(),
-        };
+    // Geometry of all shapes of a body spec, so drawing matches the physics body
+    pub fn create_draw_object_shapes(&mut self, shapes: &Vec<ShapeSpec>, color: Color) -> VertexComponent {
+        let mut vertices = vec![];
 
+        for shape in shapes.iter() {
+            vertices.extend(shape_vertices(shape, color));
+        }
 
-        return DrawSystem::new_vertex_component(vertices, Bundle::new(slice, pso, data));
+        return self.create_draw_object(vertices);
     }
 
     pub fn create_draw_object_stl(&mut self, stl: &'static [u8], color: Color) -> VertexComponent {
-        let pso = self.factory
-            .create_pipeline_simple(include_bytes!("shader/triangle_150.glslv"),
-                                    include_bytes!("shader/triangle_150.glslf"),
-                                    pipe::new())
-            .unwrap();
         use std::io::Cursor;
 
         let mut model_reader = Cursor::new(stl.iter());
@@ -279,8 +381,72 @@ impl DrawSystem {
         let vertices =
             stl_file.triangles.iter().flat_map(|t| Point::from_stl(t, color)).collect();
 
+        let bundle = match self.backend {
+            Backend::GL(ref mut gl) => Some(gl.create_bundle(vertex_count as usize)),
+            Backend::Headless(_) => None,
+        };
+
+        return DrawSystem::new_vertex_component(vertices, bundle);
+    }
+
+    pub fn create_text(&self) -> TextComponent {
+        let renderer = match self.backend {
+            Backend::GL(ref gl) => Some(gfx_text::new(gl.factory.clone()).with_size(60).unwrap()),
+            Backend::Headless(_) => None,
+        };
+
+        return TextComponent {
+            text: "".to_owned(),
+            color: [1.0, 1.0, 1.0],
+            transform: IDENTITY,
+            renderer: renderer,
+        };
+    }
+
+    pub fn resize(&mut self) -> () {
+        if let Backend::GL(ref mut gl) = self.backend {
+            gfx_window_sdl::update_views(&gl.window, &mut gl.color_view, &mut gl.depth_view);
+            self.resize = true;
+        }
+    }
+
+    pub fn pre_render(&mut self) -> () {
+        match self.backend {
+            Backend::GL(ref mut gl) => gl.encoder.clear(&gl.color_view, CLEAR_COLOR),
+            Backend::Headless(ref mut headless) => headless.draw_calls.clear(),
+        }
+    }
+
+    pub fn post_render(&mut self) -> () {
+        match self.backend {
+            Backend::GL(ref mut gl) => {
+                gl.encoder.flush(&mut gl.device);
+                gl.window.gl_swap_window();
+                gl.device.cleanup();
+            }
+            Backend::Headless(ref mut headless) => headless.frames += 1,
+        }
+        self.resize = false;
+    }
+
+    pub fn draw(&mut self, object: &mut DrawComponent) -> () {
+        match self.backend {
+            Backend::GL(ref mut gl) => object.draw(self.resize, &mut gl.encoder, &gl.color_view),
+            Backend::Headless(ref mut headless) => headless.draw_calls.push(object.draw_call()),
+        }
+    }
+}
+
+impl GLBackend {
+    fn create_bundle(&mut self, vertex_count: usize) -> Bundle<Resources, pipe::Data<Resources>> {
+        let pso = self.factory
+            .create_pipeline_simple(include_bytes!("shader/triangle_150.glslv"),
+                                    include_bytes!("shader/triangle_150.glslf"),
+                                    pipe::new())
+            .unwrap();
+
         let vertex_buffer = self.factory
-            .create_buffer(vertex_count as usize,
+            .create_buffer(vertex_count,
                            gfx::buffer::Role::Vertex,
                            gfx::memory::Usage::Dynamic,
                            gfx::Bind::empty())
@@ -298,36 +464,138 @@ impl DrawSystem {
             out: self.color_view.clone(),
         };
 
-        return DrawSystem::new_vertex_component(vertices, Bundle::new(slice, pso, data));
+        return Bundle::new(slice, pso, data);
     }
+}
 
-    pub fn create_text(&self) -> TextComponent {
-        let normal_text = gfx_text::new(self.factory.clone()).with_size(60).unwrap();
-        return TextComponent {
-            text: "".to_owned(),
-            color: [1.0, 1.0, 1.0],
-            transform: IDENTITY,
-            renderer: normal_text,
-        };
+fn circle_segments(radius: f32) -> u32 {
+    return ((radius * CIRCLE_SEGMENTS).ceil() as u32).max(MIN_CIRCLE_SEGMENTS);
+}
+
+fn circle_point(center: &WorldPoint, radius: f32, angle: f32) -> WorldPoint {
+    return [center[0] + angle.cos() * radius, center[1] + angle.sin() * radius, center[2]];
+}
+
+// Triangle fan around the center
+fn circle_vertices(center: &WorldPoint, radius: f32, color: Color) -> Vec<Point> {
+    let segments = circle_segments(radius);
+    let angle_step = (2.0 * PI) / segments as f32;
+
+    let mut vertices = vec![];
+    for n in 0..segments {
+        let angle = angle_step * n as f32;
+        let angle_prime = angle_step * (n + 1) as f32;
+
+        vertices.push(Point::from_point_and_color(center, color));
+        vertices.push(Point::from_point_and_color(&circle_point(center, radius, angle), color));
+        vertices.push(Point::from_point_and_color(&circle_point(center, radius, angle_prime), color));
     }
 
-    pub fn resize(&mut self) -> () {
-        gfx_window_sdl::update_views(&self.window, &mut self.color_view, &mut self.depth_view);
-        self.resize = true;
+    return vertices;
+}
+
+// Two triangles per segment between the inner and the outer circle
+fn ring_vertices(inner_radius: f32, outer_radius: f32, color: Color) -> Vec<Point> {
+    let center = [0.0, 0.0, 0.0];
+    let segments = circle_segments(outer_radius);
+    let angle_step = (2.0 * PI) / segments as f32;
+
+    let mut vertices = vec![];
+    for n in 0..segments {
+        let angle = angle_step * n as f32;
+        let angle_prime = angle_step * (n + 1) as f32;
+
+        let inner = circle_point(&center, inner_radius, angle);
+        let outer = circle_point(&center, outer_radius, angle);
+        let inner_prime = circle_point(&center, inner_radius, angle_prime);
+        let outer_prime = circle_point(&center, outer_radius, angle_prime);
+
+        vertices.push(Point::from_point_and_color(&inner, color));
+        vertices.push(Point::from_point_and_color(&outer, color));
+        vertices.push(Point::from_point_and_color(&inner_prime, color));
+
+        vertices.push(Point::from_point_and_color(&outer_prime, color));
+        vertices.push(Point::from_point_and_color(&outer, color));
+        vertices.push(Point::from_point_and_color(&inner_prime, color));
     }
 
-    pub fn pre_render(&mut self) -> () {
-        self.encoder.clear(&self.color_view, CLEAR_COLOR);
+    return vertices;
+}
+
+// A quad along every segment
+fn line_vertices(points: &Vec<WorldPoint>, looped: bool, width: f32, color: Color) -> Vec<Point> {
+    let mut points = points.clone();
+    if looped && points.len() > 2 {
+        let first = points[0];
+        points.push(first);
     }
 
-    pub fn post_render(&mut self) -> () {
-        self.encoder.flush(&mut self.device);
-        self.window.gl_swap_window();
-        self.device.cleanup();
-        self.resize = false;
+    let mut vertices = vec![];
+    for segment in points.windows(2) {
+        let (a, b) = (segment[0], segment[1]);
+        let (dx, dy) = (b[0] - a[0], b[1] - a[1]);
+        let length = (dx * dx + dy * dy).sqrt();
+        if length == 0.0 {
+            continue;
+        }
+
+        // Half the width along the segment's normal
+        let nx = -dy / length * width / 2.0;
+        let ny = dx / length * width / 2.0;
+
+        let a_left = [a[0] + nx, a[1] + ny, a[2]];
+        let a_right = [a[0] - nx, a[1] - ny, a[2]];
+        let b_left = [b[0] + nx, b[1] + ny, b[2]];
+        let b_right = [b[0] - nx, b[1] - ny, b[2]];
+
+        vertices.push(Point::from_point_and_color(&a_left, color));
+        vertices.push(Point::from_point_and_color(&a_right, color));
+        vertices.push(Point::from_point_and_color(&b_left, color));
+
+        vertices.push(Point::from_point_and_color(&b_right, color));
+        vertices.push(Point::from_point_and_color(&b_left, color));
+        vertices.push(Point::from_point_and_color(&a_right, color));
     }
 
-    pub fn draw(&mut self, object: &mut DrawComponent) -> () {
-        object.draw(self.resize, &mut self.encoder, &self. color_view)
+    return vertices;
+}
+
+// Triangle fan, the polygon has to be convex
+fn polygon_vertices(points: &Vec<WorldPoint>, color: Color) -> Vec<Point> {
+    let mut vertices = vec![];
+    for n in 1..points.len().saturating_sub(1) {
+        vertices.push(Point::from_point_and_color(&points[0], color));
+        vertices.push(Point::from_point_and_color(&points[n], color));
+        vertices.push(Point::from_point_and_color(&points[n + 1], color));
     }
+
+    return vertices;
+}
+
+fn shape_vertices(shape: &ShapeSpec, color: Color) -> Vec<Point> {
+    let to_world = |v: &[f32; 2]| [v[0], v[1], 0.0];
+
+    return match *shape {
+        ShapeSpec::Polygon { ref vertices } => {
+            polygon_vertices(&vertices.iter().map(|v| to_world(v)).collect(), color)
+        }
+        ShapeSpec::Box { width, height, center, angle } => {
+            let (sin, cos) = angle.sin_cos();
+            let corners = [[-width, -height], [width, -height], [width, height], [-width, height]];
+            let points = corners.iter()
+                .map(|c| {
+                    let (x, y) = (c[0] / 2.0, c[1] / 2.0);
+                    [center[0] + x * cos - y * sin, center[1] + x * sin + y * cos, 0.0]
+                })
+                .collect();
+            polygon_vertices(&points, color)
+        }
+        ShapeSpec::Circle { radius, center } => circle_vertices(&to_world(&center), radius, color),
+        ShapeSpec::Edge { from, to } => {
+            line_vertices(&vec![to_world(&from), to_world(&to)], false, LINE_WIDTH, color)
+        }
+        ShapeSpec::Chain { ref vertices, looped } => {
+            line_vertices(&vertices.iter().map(|v| to_world(v)).collect(), looped, LINE_WIDTH, color)
+        }
+    };
 }
@@ -0,0 +1,152 @@
+use config::BotConfig;
+use physics::PhysicsSystem;
+use input::{InputSystem, ID};
+use game::session::Session;
+use rng::Rng;
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    // Ticks between two decisions
+    fn reaction_ticks(&self) -> u32 {
+        match *self {
+            Difficulty::Easy => 20,
+            Difficulty::Normal => 8,
+            Difficulty::Hard => 2,
+        }
+    }
+
+    // Largest error in the stick direction, in radians
+    fn aim_error(&self) -> f32 {
+        match *self {
+            Difficulty::Easy => 0.6,
+            Difficulty::Normal => 0.25,
+            Difficulty::Hard => 0.05,
+        }
+    }
+
+    // Fraction of the full stick range used
+    fn strength(&self) -> f32 {
+        match *self {
+            Difficulty::Easy => 0.6,
+            Difficulty::Normal => 0.85,
+            Difficulty::Hard => 1.0,
+        }
+    }
+}
+
+// What a bot wants to do this tick. The stick uses world directions, x right and y up.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BotInput {
+    pub stick: [f32; 2],
+    pub button_a: bool,
+    pub button_b: bool,
+}
+
+impl BotInput {
+    pub fn idle() -> BotInput {
+        BotInput::default()
+    }
+
+    // Full stick towards a direction, zero length directions give an idle stick
+    pub fn towards(direction: [f32; 2]) -> BotInput {
+        let length = (direction[0] * direction[0] + direction[1] * direction[1]).sqrt();
+        if length == 0.0 {
+            return BotInput::idle();
+        }
+
+        let mut input = BotInput::idle();
+        input.stick = [direction[0] / length, direction[1] / length];
+        return input;
+    }
+}
+
+struct Bot {
+    id: ID,
+    difficulty: Difficulty,
+    held: BotInput,
+    wait: u32,
+}
+
+// Virtual controllers driven by the running minigame's bot strategy
+pub struct Bots {
+    bots: Vec<Bot>,
+    rng: Rng,
+}
+
+impl Bots {
+    pub fn new(seed: u64) -> Bots {
+        Bots {
+            bots: vec![],
+            rng: Rng::new(seed),
+        }
+    }
+
+    // Bots as configured in [bots]
+    pub fn from_config(config: &BotConfig, input: &mut InputSystem, seed: u64) -> Bots {
+        let mut bots = Bots::new(seed);
+        for _ in 0..config.count {
+            bots.add(input, config.difficulty);
+        }
+        return bots;
+    }
+
+    pub fn add(&mut self, input: &mut InputSystem, difficulty: Difficulty) -> ID {
+        let id = input.add_virtual_controller();
+        info!("Bot {:?} joined ({:?})", id, difficulty);
+
+        self.bots.push(Bot {
+            id: id,
+            difficulty: difficulty,
+            held: BotInput::idle(),
+            wait: 0,
+        });
+
+        return id;
+    }
+
+    // Write every bot's controller state, call before each session step
+    pub fn update(&mut self, session: &Session, physics: &PhysicsSystem, input: &mut InputSystem) {
+        for bot in self.bots.iter_mut() {
+            if bot.wait == 0 {
+                let wanted = session.bot_input(bot.id, bot.difficulty, physics);
+                bot.held = imprecise(wanted, bot.difficulty, &mut self.rng);
+                bot.wait = bot.difficulty.reaction_ticks();
+            } else {
+                bot.wait = bot.wait - 1;
+            }
+
+            if let Some(state) = input.get_controller_state_mut(bot.id) {
+                // Stick y points down
+                state.set_left_stick([bot.held.stick[0], -bot.held.stick[1]]);
+                state.button_a = bot.held.button_a;
+                state.button_b = bot.held.button_b;
+            }
+        }
+    }
+}
+
+// Add the aim error and strength limit of the difficulty
+fn imprecise(input: BotInput, difficulty: Difficulty, rng: &mut Rng) -> BotInput {
+    let error = rng.range(-difficulty.aim_error(), difficulty.aim_error());
+    let (sin, cos) = error.sin_cos();
+    let strength = difficulty.strength();
+
+    let (x, y) = (input.stick[0], input.stick[1]);
+    let mut result = input;
+    result.stick = [(x * cos - y * sin) * strength, (x * sin + y * cos) * strength];
+
+    // Clamp to the unit circle like a real stick
+    let length = (result.stick[0] * result.stick[0] + result.stick[1] * result.stick[1]).sqrt();
+    if length > 1.0 {
+        result.stick = [result.stick[0] / length, result.stick[1] / length];
+    }
+
+    return result;
+}
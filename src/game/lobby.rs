@@ -0,0 +1,188 @@
+use std::collections::HashMap;
+
+use sdl2::controller::Button;
+
+use draw::{DrawSystem, TextComponent, IDENTITY};
+use input::{InputSystem, ID, is_virtual_id};
+use game::roster::{Roster, RosterEvent, PlayerId};
+
+const NAMES: [&str; 16] = [
+    "Ace", "Bolt", "Comet", "Dash", "Echo", "Fizz", "Gizmo", "Hex",
+    "Ion", "Jinx", "Koi", "Lux", "Mojo", "Nova", "Onyx", "Pixel",
+];
+
+// How far the stick has to be pushed to count as a flick in the menu
+const FLICK: f32 = 0.6;
+
+const TITLE_COLOR: [f32; 3] = [0.9, 0.9, 0.9];
+
+struct Entry {
+    player: PlayerId,
+    ready: bool,
+    // Index into NAMES once the player picked a name, until then they go by their color
+    name: Option<usize>,
+    text: TextComponent,
+}
+
+// Where players join before the first match. Unassigned controllers join with Start or A.
+// Joined players pick a color with left/right and a name with up/down, then ready up with A.
+// B takes back ready, or leaves when not ready. Bots and scripted controllers join ready,
+// but while a person could still join they wait for at least one of them.
+pub struct Lobby {
+    title: TextComponent,
+    entries: Vec<Entry>,
+    // A controller held by a person is connected but hasn't joined
+    humans_waiting: bool,
+    // Stick direction of each controller at the last step, so holding the stick moves once
+    last_flick: HashMap<ID, (i8, i8)>,
+}
+
+impl Lobby {
+    pub fn new(draw: &DrawSystem) -> Lobby {
+        let mut title = draw.create_text();
+        title.text = "Press Start or A to join".into();
+        title.color = TITLE_COLOR;
+        title.transform = text_transform(0);
+
+        Lobby {
+            title: title,
+            entries: vec![],
+            humans_waiting: false,
+            last_flick: HashMap::new(),
+        }
+    }
+
+    // Everyone who joined is ready, and there is someone. Bots don't start on their own
+    // while a person has a controller, so they can't skip the lobby before anyone joins.
+    pub fn done(&self, roster: &Roster) -> bool {
+        let human_joined = self.entries.iter()
+            .filter_map(|e| roster.player(e.player).and_then(|p| p.controller))
+            .any(|controller| !is_virtual_id(controller));
+        return !self.entries.is_empty()
+            && self.entries.iter().all(|e| e.ready)
+            && (human_joined || !self.humans_waiting);
+    }
+
+    pub fn step(&mut self, draw: &DrawSystem, input: &InputSystem, roster: &mut Roster) {
+        // Players who lost their controller here haven't played yet, they simply leave
+        let gone: Vec<PlayerId> = roster.events().iter().filter_map(|e| match *e {
+            RosterEvent::Disconnected(id) => Some(id),
+            _ => None,
+        }).collect();
+        for id in gone {
+            roster.leave(id);
+        }
+
+        // The press that joined shouldn't also ready the player
+        let mut joined = vec![];
+        for controller in roster.unassigned() {
+            let join = is_virtual_id(controller)
+                || input.just_pressed(controller, Button::Start)
+                || input.just_pressed(controller, Button::A);
+            if !join {
+                continue;
+            }
+
+            if let Some(player) = roster.join(controller) {
+                let mut text = draw.create_text();
+                text.transform = text_transform(self.entries.len() + 1);
+                self.entries.push(Entry {
+                    player: player,
+                    ready: is_virtual_id(controller),
+                    name: None,
+                    text: text,
+                });
+                joined.push(player);
+            }
+        }
+
+        let mut leaving = vec![];
+        for entry in self.entries.iter_mut().filter(|e| !joined.contains(&e.player)) {
+            let controller = match roster.player(entry.player).and_then(|p| p.controller) {
+                Some(controller) => controller,
+                None => continue,
+            };
+            let frame = match input.frame().controller(controller) {
+                Some(frame) => *frame,
+                None => continue,
+            };
+
+            let (flick_x, flick_y) = flick(&frame.left_stick, self.last_flick.get(&controller));
+            self.last_flick.insert(controller, stick_direction(&frame.left_stick));
+
+            if entry.ready {
+                if frame.just_pressed(Button::B) {
+                    entry.ready = false;
+                }
+                continue;
+            }
+
+            if frame.just_pressed(Button::B) {
+                leaving.push(entry.player);
+            } else if frame.just_pressed(Button::A) || frame.just_pressed(Button::Start) {
+                entry.ready = true;
+            } else if frame.just_pressed(Button::DPadRight) || flick_x > 0 {
+                roster.cycle_color(entry.player, true);
+            } else if frame.just_pressed(Button::DPadLeft) || flick_x < 0 {
+                roster.cycle_color(entry.player, false);
+            } else if frame.just_pressed(Button::DPadUp) || flick_y < 0 {
+                entry.name = Some(next_name(entry.name, true));
+            } else if frame.just_pressed(Button::DPadDown) || flick_y > 0 {
+                entry.name = Some(next_name(entry.name, false));
+            }
+
+            if let Some(name) = entry.name {
+                roster.set_name(entry.player, NAMES[name].into());
+            }
+        }
+
+        for id in leaving {
+            roster.leave(id);
+        }
+        self.entries.retain(|e| roster.player(e.player).is_some());
+        self.humans_waiting = roster.unassigned().iter().any(|&controller| !is_virtual_id(controller));
+
+        for (i, entry) in self.entries.iter_mut().enumerate() {
+            if let Some(player) = roster.player(entry.player) {
+                entry.text.text = format!("{}{}", player.name, if entry.ready { " - ready" } else { "" });
+                entry.text.color = player.color;
+                entry.text.transform = text_transform(i + 1);
+            }
+        }
+    }
+
+    pub fn render(&mut self, draw: &mut DrawSystem) {
+        draw.draw(&mut self.title);
+        for entry in self.entries.iter_mut() {
+            draw.draw(&mut entry.text);
+        }
+    }
+}
+
+fn next_name(current: Option<usize>, forward: bool) -> usize {
+    return match (current, forward) {
+        (None, _) => 0,
+        (Some(i), true) => (i + 1) % NAMES.len(),
+        (Some(i), false) => (i + NAMES.len() - 1) % NAMES.len(),
+    };
+}
+
+fn stick_direction(stick: &[f32; 2]) -> (i8, i8) {
+    let axis = |value: f32| if value > FLICK { 1 } else if value < -FLICK { -1 } else { 0 };
+    return (axis(stick[0]), axis(stick[1]));
+}
+
+// Stick directions that weren't pushed at the last step, y points down
+fn flick(stick: &[f32; 2], last: Option<&(i8, i8)>) -> (i8, i8) {
+    let (x, y) = stick_direction(stick);
+    let (last_x, last_y) = last.cloned().unwrap_or((0, 0));
+    return (if x != last_x { x } else { 0 }, if y != last_y { y } else { 0 });
+}
+
+// One line per row, the title on top
+fn text_transform(row: usize) -> ::draw::Transform {
+    let mut transform = IDENTITY;
+    transform.transform[3][0] = -0.6;
+    transform.transform[3][1] = 0.6 - row as f32 * 0.15;
+    return transform;
+}
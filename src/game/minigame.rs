@@ -1,82 +1,90 @@
-use input::InputSystem;
-use draw;
+use config::AvoidTheWallConfig;
+use input::{InputSystem, ID};
+use game::bot::{BotInput, Difficulty};
+use game::roster::Roster;
 use draw::DrawSystem;
 use draw::VertexComponent;
 use physics::PhysicsSystem;
 use physics::PhysicsComponent;
-use std;
+use physics::{BodySpec, Category, CollisionFilter, LAYER_SENSOR, LAYER_ALL};
 
 pub trait MiniGame
 
 {
-    fn new(draw: &mut DrawSystem, physics: &mut PhysicsSystem, input: &InputSystem) -> Self;
     fn done(&self) -> bool;
-    fn step(&mut self, draw: &mut DrawSystem, physics: &mut PhysicsSystem, input: &mut InputSystem) -> ();
-    fn render(&mut self, draw: &mut DrawSystem) -> ();
+    // The roster is already updated for this tick, its events tell who joined or lost their controller
+    fn step(&mut self, draw: &mut DrawSystem, physics: &mut PhysicsSystem, input: &mut InputSystem, roster: &mut Roster) -> ();
+    // alpha is how far the frame is between the previous and the current step
+    fn render(&mut self, draw: &mut DrawSystem, physics: &PhysicsSystem, alpha: f32) -> ();
+    // Destroy every physics body the game created, called before the game is dropped
+    fn teardown(&mut self, physics: &mut PhysicsSystem) -> ();
+    // What a bot playing with this controller should do, games without a strategy leave bots idle
+    fn bot_input(&self, _controller: ID, _difficulty: Difficulty, _physics: &PhysicsSystem) -> BotInput {
+        return BotInput::idle();
+    }
 }
 
-//World types
-pub type Point = [f32; 3];
-pub type Color = [f32; 3];
+// Boxed constructor so minigames can be listed and created by name. Games seed their own Rng
+// with the seed they are given, never from the clock, so matches can be replayed.
+pub type MiniGameConstructor = fn(&mut DrawSystem, &mut PhysicsSystem, &Roster, &MiniGameSettings, u64) -> Box<MiniGame>;
 
-pub fn create_ring(id: f32, od: f32, color: Color, draw: &mut DrawSystem, physics: &mut PhysicsSystem) -> (VertexComponent, PhysicsComponent) {
-    let pi = std::f32::consts::PI;
+// Tuning of the minigames from settings.toml, replays record it with the seed
+#[derive(Debug, Clone)]
+pub struct MiniGameSettings {
+    pub avoid_the_wall: AvoidTheWallConfig,
+}
 
-    let mut vertices: Vec<draw::Point> = vec![];
-    let mut bounding: Vec<Point> = vec![];
+// Seconds a minigame waits for someone to reconnect once every player lost their controller
+const ABANDON_SECONDS: f32 = 10.0;
 
-    //Number of points along the edge
-    let steps: i32 = 128;
-    let angle_step = (2.0 * pi) / steps as f32;
-    //TODO fix fudge factor
-    let bounding_diameter = od * 1.15;
+// What every minigame does when all players are disconnected: it pauses, and gives up
+// without a winner if nobody is back within ABANDON_SECONDS
+pub struct Abandonment {
+    // Seconds since the last player disconnected, 0 while someone is connected
+    waited: f32,
+}
 
-    //Physics bounds
-    for n in 0..steps {
-        let angle = angle_step * n as f32;
-        bounding.push([angle.sin() * bounding_diameter, angle.cos() * bounding_diameter, 0.0]);
+impl Abandonment {
+    pub fn new() -> Abandonment {
+        Abandonment { waited: 0.0 }
     }
 
-    //Graphics vertices
-    for n in 0..steps {
-        let angle = angle_step * n as f32;
-        let angle_prime = angle_step * (n + 1) as f32;
-
-        let z = 0f32;
-
-        let p1 = draw::Point {
-            pos: [angle.sin() * id, angle.cos() * id, z],
-            color: color,
-
-        };
-
-        let p2 = draw::Point {
-            pos: [angle.sin() * od, angle.cos() * od, z],
-            color: color,
-        };
-
-        let p3 = draw::Point {
-            pos: [angle_prime.sin() * id, angle_prime.cos() * id, z],
-            color: color,
-        };
-
-        vertices.push(p1);
-        vertices.push(p2);
-        vertices.push(p3);
+    // Call once per step after handling the roster events. While it returns true the game
+    // skips the rest of its step, physics included.
+    pub fn paused(&mut self, roster: &Roster, dt: f32) -> bool {
+        if roster.players().iter().any(|p| p.connected()) {
+            self.waited = 0.0;
+            return false;
+        }
+
+        if self.waited == 0.0 {
+            info!("Everyone disconnected, pausing");
+        }
+        self.waited = self.waited + dt;
+        if self.expired() {
+            info!("Nobody came back, ending without a winner");
+        }
+        return true;
+    }
 
-        let p1 = draw::Point {
-            pos: [angle_prime.sin() * od, angle_prime.cos() * od, z],
-            color: color,
+    // The game should report done
+    pub fn expired(&self) -> bool {
+        return self.waited >= ABANDON_SECONDS;
+    }
+}
 
-        };
+//World types
+pub type Point = [f32; 3];
+pub type Color = [f32; 3];
 
-        vertices.push(p1);
-        vertices.push(p2);
-        vertices.push(p3);
-    }
+// Ring drawn between the inner and outer diameter, with a sensor covering the whole arena.
+// Players have left the arena once their contact with the sensor ends.
+pub fn create_ring(id: f32, od: f32, color: Color, draw: &mut DrawSystem, physics: &mut PhysicsSystem) -> (VertexComponent, PhysicsComponent) {
+    let mut spec = BodySpec::default();
+    spec.sensor = true;
 
-    let draw_object = draw.create_draw_object(vertices);
-    let physics_object = physics.create_boundary_sensor(&bounding);
+    let draw_object = draw.create_ring(id, od, color);
+    let physics_object = physics.create_circle(od, &spec, Category::Boundary, CollisionFilter::new(LAYER_SENSOR, LAYER_ALL));
 
     return (draw_object, physics_object);
 }
@@ -0,0 +1,421 @@
+use std::f32;
+use std::f32::consts::PI;
+
+use config::AvoidTheWallConfig;
+use game::minigame::{MiniGame, MiniGameSettings, Abandonment, Color, Point};
+use game::bot::{BotInput, Difficulty};
+use game::roster::{Roster, RosterPlayer, RosterEvent, PlayerId};
+use physics::{PhysicsSystem, PhysicsComponent, CollisionEvent, Category, CollisionFilter};
+use physics::{BodySpec, BodySpecs, ShapeSpec};
+use physics::spec::BODIES_FILE;
+use physics::{LAYER_PLAYER, LAYER_WALL, LAYER_PROJECTILE, LAYER_ALL};
+use draw::{DrawSystem, DrawComponent, VertexComponent, TextComponent, IDENTITY};
+use input::{InputSystem, ID};
+use rng::Rng;
+
+const PLAYER_RADIUS: f32 = 0.04;
+// Stick force on a player, world units like apply_force_to_center
+const MOVE_FORCE: f32 = 1.0;
+
+// Walls start this far out on one side and are removed once this far out on the other
+const WALL_DISTANCE: f32 = 1.3;
+// Long enough to cross the whole arena at any angle
+const WALL_HALF_LENGTH: f32 = 1.5;
+const WALL_THICKNESS: f32 = 0.05;
+// Furthest the gap is from the middle of the wall
+const GAP_RANGE: f32 = 0.6;
+// Wider gaps wouldn't leave any wall on one side when the gap is furthest out
+const MAX_GAP: f32 = 2.0 * (WALL_HALF_LENGTH - GAP_RANGE - WALL_THICKNESS);
+// Keep a ramp from settings.toml from stalling walls or spawning one every tick
+const MIN_SPEED: f32 = 0.05;
+const MIN_INTERVAL: f32 = 0.2;
+
+const BORDER_COLOR: Color = [0.5, 0.5, 0.5];
+const WALL_COLOR: Color = [0.8, 0.8, 0.8];
+const DEAD_COLOR: Color = [0.05, 0.05, 0.05];
+
+struct Player {
+    player_id: PlayerId,
+    name: String,
+    // None while the controller is disconnected, the player is paused and can't be hit
+    controller: Option<ID>,
+    alive: bool,
+    draw_component: VertexComponent,
+    physics_component: PhysicsComponent,
+}
+
+// A wall sliding across the arena with a gap in it
+struct Wall {
+    // Direction the wall moves in
+    direction: [f32; 2],
+    // Along the wall, from the middle to the gap
+    gap_offset: f32,
+    draw_component: VertexComponent,
+    physics_component: PhysicsComponent,
+}
+
+impl Wall {
+    // Along the wall, the body's x axis in world coordinates
+    fn axis(&self) -> [f32; 2] {
+        return [self.direction[1], -self.direction[0]];
+    }
+}
+
+// Walls with a gap sweep through the arena from random directions. Touching one is out,
+// the last player left wins. Walls get faster, more frequent and narrower over time.
+pub struct AvoidTheWall {
+    ramp: AvoidTheWallConfig,
+    players: Vec<Player>,
+    walls: Vec<Wall>,
+    border: VertexComponent,
+    border_component: PhysicsComponent,
+    player_spec: BodySpec,
+    wall_spec: BodySpec,
+    rng: Rng,
+    // Walls spawned so far, drives the ramp
+    spawned: u32,
+    // Seconds until the next wall
+    next_wall: f32,
+    wall_text: TextComponent,
+    winner: Option<Option<PlayerId>>,
+    abandonment: Abandonment,
+}
+
+impl AvoidTheWall {
+    pub fn create(draw: &mut DrawSystem, physics: &mut PhysicsSystem, roster: &Roster, settings: &MiniGameSettings, seed: u64) -> Box<MiniGame> {
+        return Box::new(AvoidTheWall::new(draw, physics, roster, settings.avoid_the_wall.clone(), seed));
+    }
+
+    pub fn new(draw: &mut DrawSystem,
+               physics: &mut PhysicsSystem,
+               roster: &Roster,
+               ramp: AvoidTheWallConfig,
+               seed: u64) -> AvoidTheWall {
+        let ramp = checked_ramp(&ramp);
+        let specs = BodySpecs::load(BODIES_FILE).expect("Failed to load body specs");
+
+        let corners = vec![[-0.98, -0.98, 0.0], [0.98, -0.98, 0.0], [0.98, 0.98, 0.0], [-0.98, 0.98, 0.0]];
+        let border = draw.create_line(&corners, true, 0.02, BORDER_COLOR);
+        let border_component = physics.create_chain(&corners, true, &BodySpec::default(), Category::Wall, CollisionFilter::new(LAYER_WALL, LAYER_ALL));
+
+        let mut wall_text = draw.create_text();
+        wall_text.color = WALL_COLOR;
+        let mut transform = IDENTITY;
+        transform.transform[3][0] = -0.1;
+        transform.transform[3][1] = 0.9;
+        wall_text.transform = transform;
+
+        let mut game = AvoidTheWall {
+            next_wall: ramp.start_interval,
+            ramp: ramp,
+            players: vec![],
+            walls: vec![],
+            border: border,
+            border_component: border_component,
+            player_spec: specs.get("avoid_player"),
+            wall_spec: specs.get("avoid_wall"),
+            rng: Rng::new(seed),
+            spawned: 0,
+            wall_text: wall_text,
+            winner: None,
+            abandonment: Abandonment::new(),
+        };
+
+        for roster_player in roster.players() {
+            game.add_player(draw, physics, roster_player);
+        }
+
+        return game;
+    }
+
+    fn add_player(&mut self, draw: &mut DrawSystem, physics: &mut PhysicsSystem, roster_player: &RosterPlayer) {
+        if self.players.iter().any(|p| p.player_id == roster_player.id) {
+            return;
+        }
+
+        // On a circle around the center
+        let angle = self.players.len() as f32 * PI / 4.0;
+        let start = [angle.cos() * 0.3, angle.sin() * 0.3, 0.0];
+
+        let physics_object = physics.create_circle(PLAYER_RADIUS, &self.player_spec, Category::Player, CollisionFilter::new(LAYER_PLAYER, LAYER_ALL));
+        physics.set_transform(&physics_object, start, 0.0);
+        if roster_player.controller.is_none() {
+            physics.set_active(&physics_object, false);
+        }
+
+        self.players.push(Player {
+            player_id: roster_player.id,
+            name: roster_player.name.clone(),
+            controller: roster_player.controller,
+            alive: true,
+            draw_component: draw.create_circle(&[0.0, 0.0, 0.0], PLAYER_RADIUS, roster_player.color),
+            physics_component: physics_object,
+        });
+        info!("New player added to avoid the wall");
+    }
+
+    fn set_controller(&mut self, physics: &mut PhysicsSystem, player_id: PlayerId, controller: Option<ID>) {
+        for player in self.players.iter_mut().filter(|p| p.player_id == player_id) {
+            player.controller = controller;
+            if player.alive {
+                physics.set_active(&player.physics_component, controller.is_some());
+            }
+        }
+    }
+
+    fn remove_player(&mut self, physics: &mut PhysicsSystem, player_id: PlayerId) {
+        for player in self.players.iter().filter(|p| p.player_id == player_id) {
+            physics.destroy_body(&player.physics_component);
+        }
+        self.players.retain(|p| p.player_id != player_id);
+    }
+
+    // Next wall from a random side with the gap somewhere along it, moving at the ramp's speed
+    fn spawn_wall(&mut self, draw: &mut DrawSystem, physics: &mut PhysicsSystem) {
+        let n = self.spawned as f32;
+        let speed = (self.ramp.start_speed + self.ramp.speed_step * n).min(self.ramp.max_speed);
+        let gap = (self.ramp.start_gap - self.ramp.gap_step * n).max(self.ramp.min_gap);
+        let interval = (self.ramp.start_interval - self.ramp.interval_step * n).max(self.ramp.min_interval);
+
+        let heading = self.rng.range(0.0, 2.0 * PI);
+        let direction = [heading.cos(), heading.sin()];
+        let gap_offset = self.rng.range(-GAP_RANGE, GAP_RANGE);
+
+        // Built along the x axis, then turned so y points in the direction of travel
+        let left = gap_offset - gap / 2.0 + WALL_HALF_LENGTH;
+        let right = WALL_HALF_LENGTH - gap_offset - gap / 2.0;
+        let shapes = vec![
+            ShapeSpec::Box { width: left, height: WALL_THICKNESS, center: [-WALL_HALF_LENGTH + left / 2.0, 0.0], angle: 0.0 },
+            ShapeSpec::Box { width: right, height: WALL_THICKNESS, center: [WALL_HALF_LENGTH - right / 2.0, 0.0], angle: 0.0 },
+        ];
+
+        let mut spec = self.wall_spec.clone();
+        spec.shapes = shapes;
+        let physics_object = physics.create_body(&spec, Category::Wall, CollisionFilter::new(LAYER_PROJECTILE, LAYER_PLAYER));
+        physics.set_transform(&physics_object,
+                              [-direction[0] * WALL_DISTANCE, -direction[1] * WALL_DISTANCE, 0.0],
+                              heading - PI / 2.0);
+        physics.set_velocity(&physics_object, [direction[0] * speed, direction[1] * speed, 0.0]);
+
+        self.walls.push(Wall {
+            direction: direction,
+            gap_offset: gap_offset,
+            draw_component: draw.create_draw_object_shapes(&spec.shapes, WALL_COLOR),
+            physics_component: physics_object,
+        });
+
+        self.spawned = self.spawned + 1;
+        self.next_wall = self.next_wall + interval;
+    }
+
+    // Walls that made it all the way across
+    fn remove_passed_walls(&mut self, physics: &mut PhysicsSystem) {
+        let mut kept = vec![];
+        for wall in self.walls.drain(..) {
+            let position = physics.position(&wall.physics_component);
+            let travelled = position[0] * wall.direction[0] + position[1] * wall.direction[1];
+            if travelled > WALL_DISTANCE {
+                physics.destroy_body(&wall.physics_component);
+            } else {
+                kept.push(wall);
+            }
+        }
+        self.walls = kept;
+    }
+
+    fn round_over(&self) -> bool {
+        let active: Vec<&Player> = self.players.iter().filter(|p| p.controller.is_some()).collect();
+        let alive = active.iter().filter(|p| p.alive).count();
+        return match active.len() {
+            0 => false,
+            1 => alive == 0,
+            _ => alive <= 1,
+        };
+    }
+}
+
+impl MiniGame for AvoidTheWall {
+    fn done(&self) -> bool {
+        return self.winner.is_some() || self.abandonment.expired();
+    }
+
+    fn step(&mut self, draw: &mut DrawSystem, physics: &mut PhysicsSystem, input: &mut InputSystem, roster: &mut Roster) {
+        for event in roster.events() {
+            match *event {
+                RosterEvent::Joined(id) => {
+                    if let Some(roster_player) = roster.player(id) {
+                        self.add_player(draw, physics, roster_player);
+                    }
+                }
+                RosterEvent::Disconnected(id) => self.set_controller(physics, id, None),
+                RosterEvent::Reconnected(id) => {
+                    let controller = roster.player(id).and_then(|p| p.controller);
+                    self.set_controller(physics, id, controller);
+                }
+                RosterEvent::Left(id) => self.remove_player(physics, id),
+            }
+        }
+
+        if self.winner.is_some() {
+            return;
+        }
+
+        if self.abandonment.paused(roster, physics.time_step()) {
+            return;
+        }
+
+        for player in self.players.iter().filter(|p| p.alive) {
+            let frame = match player.controller.and_then(|id| input.frame().controller(id)) {
+                Some(frame) => *frame,
+                None => continue,
+            };
+            let force = [frame.left_stick[0] * MOVE_FORCE, -frame.left_stick[1] * MOVE_FORCE, 0.0];
+            physics.apply_force_to_center(force, &player.physics_component);
+        }
+
+        self.next_wall = self.next_wall - physics.time_step();
+        if self.next_wall <= 0.0 {
+            self.spawn_wall(draw, physics);
+        }
+
+        physics.step();
+
+        // Any contact with a moving wall is out
+        let mut hit = vec![];
+        for collision in physics.collisions() {
+            if let CollisionEvent::Began { .. } = *collision {
+                for wall in self.walls.iter() {
+                    if let Some(entity) = collision.other(wall.physics_component.entity()) {
+                        hit.push(entity);
+                    }
+                }
+            }
+        }
+
+        for player in self.players.iter_mut() {
+            if player.alive && player.controller.is_some() && hit.contains(&player.physics_component.entity()) {
+                info!("{} hit a wall", player.name);
+                player.alive = false;
+                player.draw_component.set_color(DEAD_COLOR);
+                physics.set_active(&player.physics_component, false);
+                if let Some(id) = player.controller {
+                    input.rumble(id, 1.0, 400);
+                }
+            }
+        }
+
+        self.remove_passed_walls(physics);
+
+        if self.round_over() {
+            let winner = self.players.iter()
+                .filter(|p| p.alive && p.controller.is_some())
+                .next();
+            if let Some(player) = winner {
+                info!("{} survived {} walls", player.name, self.spawned);
+                roster.add_score(player.player_id, 1);
+            }
+            self.winner = Some(winner.map(|p| p.player_id));
+        }
+
+        self.wall_text.text = format!("{}", self.spawned);
+    }
+
+    fn render(&mut self, draw: &mut DrawSystem, physics: &PhysicsSystem, alpha: f32) {
+        for player in self.players.iter_mut() {
+            player.draw_component.transform.transform =
+                physics.get_interpolated_transformation(&player.physics_component, alpha);
+        }
+        for wall in self.walls.iter_mut() {
+            wall.draw_component.transform.transform =
+                physics.get_interpolated_transformation(&wall.physics_component, alpha);
+        }
+
+        draw.draw(&mut self.border);
+
+        for wall in self.walls.iter_mut() {
+            draw.draw(&mut wall.draw_component);
+        }
+
+        for player in self.players.iter_mut() {
+            draw.draw(&mut player.draw_component);
+        }
+
+        draw.draw(&mut self.wall_text);
+    }
+
+    // Line up with the gap of the closest wall still coming, drift back to the center otherwise
+    fn bot_input(&self, controller: ID, difficulty: Difficulty, physics: &PhysicsSystem) -> BotInput {
+        let player = match self.players.iter().filter(|p| p.alive && p.controller == Some(controller)).next() {
+            Some(player) => player,
+            None => return BotInput::idle(),
+        };
+
+        let position = physics.position(&player.physics_component);
+        let mut direction = [-position[0] * 0.3, -position[1] * 0.3];
+
+        // Easier bots only react to walls that are already close
+        let sight = match difficulty {
+            Difficulty::Easy => 0.5,
+            Difficulty::Normal => 1.0,
+            Difficulty::Hard => 2.0,
+        };
+
+        let coming = self.walls.iter()
+            .map(|wall| {
+                let wall_position = physics.position(&wall.physics_component);
+                let offset = [position[0] - wall_position[0], position[1] - wall_position[1]];
+                let ahead = offset[0] * wall.direction[0] + offset[1] * wall.direction[1];
+                (wall, wall_position, ahead)
+            })
+            .filter(|&(_, _, ahead)| ahead > -PLAYER_RADIUS && ahead < sight)
+            .fold(None, |closest: Option<(&Wall, Point, f32)>, (wall, wall_position, ahead)| match closest {
+                Some((_, _, best)) if best <= ahead => closest,
+                _ => Some((wall, wall_position, ahead)),
+            });
+
+        if let Some((wall, wall_position, _)) = coming {
+            let axis = wall.axis();
+            let along = (position[0] - wall_position[0]) * axis[0] + (position[1] - wall_position[1]) * axis[1];
+            let error = wall.gap_offset - along;
+            direction = [axis[0] * error * 4.0, axis[1] * error * 4.0];
+        }
+
+        return BotInput::towards(direction);
+    }
+
+    fn teardown(&mut self, physics: &mut PhysicsSystem) {
+        physics.destroy_body(&self.border_component);
+
+        for wall in self.walls.drain(..) {
+            physics.destroy_body(&wall.physics_component);
+        }
+
+        for player in self.players.drain(..) {
+            physics.destroy_body(&player.physics_component);
+        }
+    }
+}
+
+// The ramp from settings.toml with every value moved into the range the game can play
+fn checked_ramp(ramp: &AvoidTheWallConfig) -> AvoidTheWallConfig {
+    return AvoidTheWallConfig {
+        start_speed: checked("start_speed", ramp.start_speed, MIN_SPEED, f32::MAX),
+        speed_step: checked("speed_step", ramp.speed_step, 0.0, f32::MAX),
+        max_speed: checked("max_speed", ramp.max_speed, MIN_SPEED, f32::MAX),
+        start_interval: checked("start_interval", ramp.start_interval, MIN_INTERVAL, f32::MAX),
+        interval_step: checked("interval_step", ramp.interval_step, 0.0, f32::MAX),
+        min_interval: checked("min_interval", ramp.min_interval, MIN_INTERVAL, f32::MAX),
+        start_gap: checked("start_gap", ramp.start_gap, 2.0 * PLAYER_RADIUS, MAX_GAP),
+        gap_step: checked("gap_step", ramp.gap_step, 0.0, f32::MAX),
+        min_gap: checked("min_gap", ramp.min_gap, 2.0 * PLAYER_RADIUS, MAX_GAP),
+    };
+}
+
+fn checked(name: &str, value: f32, min: f32, max: f32) -> f32 {
+    if !(value >= min && value <= max) {
+        let clamped = if value > max { max } else { min };
+        warn!("avoid_the_wall.{} = {} is out of range, using {}", name, value, clamped);
+        return clamped;
+    }
+    return value;
+}
@@ -0,0 +1,417 @@
+extern crate toml;
+
+use std::cmp::Ordering;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+
+use sdl2::controller::Button;
+
+use game::minigame::{MiniGame, MiniGameSettings, Abandonment, Color, Point};
+use game::bot::{BotInput, Difficulty};
+use game::roster::{Roster, RosterPlayer, RosterEvent, PlayerId};
+use physics::{PhysicsSystem, PhysicsComponent, CollisionEvent, Category, CollisionFilter};
+use physics::{BodySpec, BodySpecs, ShapeSpec, JointKind, JointSpec, JointComponent};
+use physics::spec::BODIES_FILE;
+use physics::{LAYER_PLAYER, LAYER_WALL, LAYER_DEFAULT, LAYER_ALL};
+use draw::{DrawSystem, VertexComponent, TextComponent, IDENTITY};
+use input::{InputSystem, ID};
+
+pub const LEVEL_FILE: &str = "levels/couch_maze.toml";
+
+const MOVER_RADIUS: f32 = 0.04;
+// Stick force on a mover, world units like apply_force_to_center
+const MOVE_FORCE: f32 = 1.0;
+// Yanking harder than this tears a grip loose, physics units
+const LATCH_BREAK_FORCE: f32 = 50.0;
+// Points for the team when the couch arrives right away, it gets less the longer it takes
+const MAX_POINTS: f32 = 5.0;
+
+const WALL_COLOR: Color = [0.5, 0.5, 0.5];
+const COUCH_COLOR: Color = [0.6, 0.35, 0.15];
+const GOAL_COLOR: Color = [0.2, 0.2, 0.2];
+const TIMER_COLOR: Color = [0.9, 0.9, 0.9];
+
+#[derive(Debug, Deserialize)]
+pub struct CouchSpec {
+    pub position: [f32; 2],
+    pub width: f32,
+    pub height: f32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Area {
+    pub center: [f32; 2],
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Area {
+    fn contains(&self, point: Point) -> bool {
+        return (point[0] - self.center[0]).abs() <= self.width / 2.0
+            && (point[1] - self.center[1]).abs() <= self.height / 2.0;
+    }
+}
+
+// A couch maze level, see levels/couch_maze.toml
+#[derive(Debug, Deserialize)]
+pub struct Level {
+    // Seconds
+    pub time_limit: f32,
+    pub starts: Vec<[f32; 2]>,
+    // Where bots push the couch, in order
+    pub route: Vec<[f32; 2]>,
+    pub couch: CouchSpec,
+    pub goal: Area,
+    pub walls: Vec<ShapeSpec>,
+}
+
+#[derive(Debug)]
+pub enum LevelError {
+    Io(io::Error),
+    Parse(toml::de::Error),
+    // Parsed, but the game can't be built from it
+    Invalid(String),
+}
+
+impl Level {
+    // The file can be edited by players, so anything the game can't build is an error here
+    pub fn load(path: &str) -> Result<Level, LevelError> {
+        let mut input = String::new();
+
+        File::open(path.to_string()).and_then(|mut f| {
+            f.read_to_string(&mut input)
+        }).map_err(LevelError::Io)?;
+
+        let level: Level = toml::from_str(&input).map_err(LevelError::Parse)?;
+
+        if level.starts.is_empty() {
+            return Err(LevelError::Invalid("no starts".into()));
+        }
+        if !(level.couch.width > 0.0 && level.couch.height > 0.0) {
+            return Err(LevelError::Invalid(format!("couch of {} by {}", level.couch.width, level.couch.height)));
+        }
+        if !(level.time_limit > 0.0) {
+            return Err(LevelError::Invalid(format!("time limit of {} seconds", level.time_limit)));
+        }
+        for (i, wall) in level.walls.iter().enumerate() {
+            if let Some(problem) = wall.problem() {
+                return Err(LevelError::Invalid(format!("wall {}: {}", i, problem)));
+            }
+        }
+
+        return Ok(level);
+    }
+}
+
+struct Mover {
+    player_id: PlayerId,
+    name: String,
+    // None while the controller is disconnected, the mover lets go and waits
+    controller: Option<ID>,
+    touching_couch: bool,
+    latch: Option<JointComponent>,
+    draw_component: VertexComponent,
+    physics_component: PhysicsComponent,
+}
+
+struct Body {
+    draw_component: VertexComponent,
+    physics_component: PhysicsComponent,
+}
+
+// Everyone works together to get the couch through the maze into the goal. Movers push it,
+// or grab it with A where they touch it and pull it along. A again or B lets go.
+pub struct CouchMaze {
+    level: Level,
+    movers: Vec<Mover>,
+    couch: Body,
+    walls: Body,
+    goal: VertexComponent,
+    mover_spec: BodySpec,
+    timer: TextComponent,
+    ticks: u32,
+    over: bool,
+    abandonment: Abandonment,
+}
+
+impl CouchMaze {
+    // The level is fixed, the seed is unused
+    pub fn create(draw: &mut DrawSystem, physics: &mut PhysicsSystem, roster: &Roster, _settings: &MiniGameSettings, _seed: u64) -> Box<MiniGame> {
+        return Box::new(CouchMaze::new(draw, physics, roster));
+    }
+
+    pub fn new(draw: &mut DrawSystem, physics: &mut PhysicsSystem, roster: &Roster) -> CouchMaze {
+        let level = Level::load(LEVEL_FILE).expect("Failed to load couch maze level");
+        let specs = BodySpecs::load(BODIES_FILE).expect("Failed to load body specs");
+
+        let mut wall_spec = specs.get("couch_wall");
+        wall_spec.shapes.extend(level.walls.iter().cloned());
+        let walls = Body {
+            draw_component: draw.create_draw_object_shapes(&wall_spec.shapes, WALL_COLOR),
+            physics_component: physics.create_body(&wall_spec, Category::Wall, CollisionFilter::new(LAYER_WALL, LAYER_ALL)),
+        };
+
+        let couch_spec = specs.get("couch").with_shape(ShapeSpec::Box {
+            width: level.couch.width,
+            height: level.couch.height,
+            center: [0.0, 0.0],
+            angle: 0.0,
+        });
+        let couch_object = physics.create_body(&couch_spec, Category::Prop, CollisionFilter::new(LAYER_DEFAULT, LAYER_ALL));
+        physics.set_transform(&couch_object, [level.couch.position[0], level.couch.position[1], 0.0], 0.0);
+        let couch = Body {
+            draw_component: draw.create_draw_object_shapes(&couch_spec.shapes, COUCH_COLOR),
+            physics_component: couch_object,
+        };
+
+        let goal_shape = ShapeSpec::Box {
+            width: level.goal.width,
+            height: level.goal.height,
+            center: level.goal.center,
+            angle: 0.0,
+        };
+        let goal = draw.create_draw_object_shapes(&vec![goal_shape], GOAL_COLOR);
+
+        let mut timer = draw.create_text();
+        timer.color = TIMER_COLOR;
+        let mut transform = IDENTITY;
+        transform.transform[3][0] = -0.1;
+        transform.transform[3][1] = 0.9;
+        timer.transform = transform;
+
+        let mut maze = CouchMaze {
+            level: level,
+            movers: vec![],
+            couch: couch,
+            walls: walls,
+            goal: goal,
+            mover_spec: specs.get("couch_mover"),
+            timer: timer,
+            ticks: 0,
+            over: false,
+            abandonment: Abandonment::new(),
+        };
+
+        for roster_player in roster.players() {
+            maze.add_mover(draw, physics, roster_player);
+        }
+
+        return maze;
+    }
+
+    fn add_mover(&mut self, draw: &mut DrawSystem, physics: &mut PhysicsSystem, roster_player: &RosterPlayer) {
+        if self.movers.iter().any(|m| m.player_id == roster_player.id) {
+            return;
+        }
+
+        let start = self.level.starts[self.movers.len() % self.level.starts.len()];
+        let physics_object = physics.create_circle(MOVER_RADIUS, &self.mover_spec, Category::Player, CollisionFilter::new(LAYER_PLAYER, LAYER_ALL));
+        physics.set_transform(&physics_object, [start[0], start[1], 0.0], 0.0);
+        if roster_player.controller.is_none() {
+            physics.set_active(&physics_object, false);
+        }
+
+        self.movers.push(Mover {
+            player_id: roster_player.id,
+            name: roster_player.name.clone(),
+            controller: roster_player.controller,
+            touching_couch: false,
+            latch: None,
+            draw_component: draw.create_circle(&[0.0, 0.0, 0.0], MOVER_RADIUS, roster_player.color),
+            physics_component: physics_object,
+        });
+        info!("New player added to couch maze");
+    }
+
+    fn set_controller(&mut self, physics: &mut PhysicsSystem, player_id: PlayerId, controller: Option<ID>) {
+        for mover in self.movers.iter_mut().filter(|m| m.player_id == player_id) {
+            if controller.is_none() {
+                if let Some(latch) = mover.latch.take() {
+                    physics.destroy_joint(&latch);
+                }
+            }
+            mover.controller = controller;
+            physics.set_active(&mover.physics_component, controller.is_some());
+        }
+    }
+
+    fn remove_mover(&mut self, physics: &mut PhysicsSystem, player_id: PlayerId) {
+        // Destroying the body takes its latch with it
+        for mover in self.movers.iter().filter(|m| m.player_id == player_id) {
+            physics.destroy_body(&mover.physics_component);
+        }
+        self.movers.retain(|m| m.player_id != player_id);
+    }
+
+    fn control(&mut self, physics: &mut PhysicsSystem, input: &InputSystem) {
+        for mover in self.movers.iter_mut() {
+            let frame = match mover.controller.and_then(|id| input.frame().controller(id)) {
+                Some(frame) => *frame,
+                None => continue,
+            };
+
+            let force = [frame.left_stick[0] * MOVE_FORCE, -frame.left_stick[1] * MOVE_FORCE, 0.0];
+            physics.apply_force_to_center(force, &mover.physics_component);
+
+            let release = frame.just_pressed(Button::B) || (frame.just_pressed(Button::A) && mover.latch.is_some());
+            if release {
+                if let Some(latch) = mover.latch.take() {
+                    physics.destroy_joint(&latch);
+                }
+            } else if frame.just_pressed(Button::A) && mover.touching_couch {
+                // Hinged where the mover holds it, so the couch can still turn
+                let anchor = physics.position(&mover.physics_component);
+                let spec = JointSpec::new(JointKind::Revolute { anchor: anchor, limits: None, motor: None })
+                    .breaking_above(LATCH_BREAK_FORCE);
+                mover.latch = Some(physics.create_joint(&mover.physics_component, &self.couch.physics_component, &spec));
+            }
+        }
+    }
+
+    fn update_contacts(&mut self, physics: &PhysicsSystem, input: &mut InputSystem) {
+        let couch = self.couch.physics_component.entity();
+        for collision in physics.collisions() {
+            let touching = match *collision {
+                CollisionEvent::Began { .. } => true,
+                CollisionEvent::Ended { .. } => false,
+                _ => continue,
+            };
+            for mover in self.movers.iter_mut() {
+                if collision.other(couch) == Some(mover.physics_component.entity()) {
+                    mover.touching_couch = touching;
+                }
+            }
+        }
+
+        for &broken in physics.broken_joints() {
+            for mover in self.movers.iter_mut() {
+                if mover.latch.as_ref().map(|l| l.id()) == Some(broken) {
+                    mover.latch = None;
+                    info!("{} lost their grip", mover.name);
+                    if let Some(id) = mover.controller {
+                        input.rumble(id, 0.6, 200);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl MiniGame for CouchMaze {
+    fn done(&self) -> bool {
+        return self.over || self.abandonment.expired();
+    }
+
+    fn step(&mut self, draw: &mut DrawSystem, physics: &mut PhysicsSystem, input: &mut InputSystem, roster: &mut Roster) {
+        for event in roster.events() {
+            match *event {
+                RosterEvent::Joined(id) => {
+                    if let Some(roster_player) = roster.player(id) {
+                        self.add_mover(draw, physics, roster_player);
+                    }
+                }
+                RosterEvent::Disconnected(id) => self.set_controller(physics, id, None),
+                RosterEvent::Reconnected(id) => {
+                    let controller = roster.player(id).and_then(|p| p.controller);
+                    self.set_controller(physics, id, controller);
+                }
+                RosterEvent::Left(id) => self.remove_mover(physics, id),
+            }
+        }
+
+        if self.over {
+            return;
+        }
+
+        // The time limit doesn't run down while paused
+        if self.abandonment.paused(roster, physics.time_step()) {
+            return;
+        }
+
+        self.control(physics, input);
+
+        physics.step();
+        self.ticks = self.ticks + 1;
+
+        self.update_contacts(physics, input);
+
+        let elapsed = self.ticks as f32 * physics.time_step();
+        let delivered = self.level.goal.contains(physics.position(&self.couch.physics_component));
+
+        if delivered {
+            // The whole team shares the points, faster deliveries are worth more
+            let left = ((self.level.time_limit - elapsed) / self.level.time_limit).max(0.0);
+            let points = 1 + (left * (MAX_POINTS - 1.0)).round() as u64;
+            info!("Couch delivered after {:.1} seconds, {} points each", elapsed, points);
+            for mover in self.movers.iter().filter(|m| m.controller.is_some()) {
+                roster.add_score(mover.player_id, points);
+            }
+            self.over = true;
+        } else if elapsed >= self.level.time_limit {
+            info!("Time is up, the couch didn't make it");
+            self.over = true;
+        }
+
+        self.timer.text = format!("{:.0}", (self.level.time_limit - elapsed).max(0.0).ceil());
+    }
+
+    fn render(&mut self, draw: &mut DrawSystem, physics: &PhysicsSystem, alpha: f32) {
+        self.couch.draw_component.transform.transform =
+            physics.get_interpolated_transformation(&self.couch.physics_component, alpha);
+        for mover in self.movers.iter_mut() {
+            mover.draw_component.transform.transform =
+                physics.get_interpolated_transformation(&mover.physics_component, alpha);
+        }
+
+        draw.draw(&mut self.goal);
+        draw.draw(&mut self.walls.draw_component);
+        draw.draw(&mut self.couch.draw_component);
+
+        for mover in self.movers.iter_mut() {
+            draw.draw(&mut mover.draw_component);
+        }
+
+        draw.draw(&mut self.timer);
+    }
+
+    // Grab the couch, then pull it towards the route point after the one it is closest to
+    fn bot_input(&self, controller: ID, difficulty: Difficulty, physics: &PhysicsSystem) -> BotInput {
+        let mover = match self.movers.iter().filter(|m| m.controller == Some(controller)).next() {
+            Some(mover) => mover,
+            None => return BotInput::idle(),
+        };
+
+        let position = physics.position(&mover.physics_component);
+        let couch = physics.position(&self.couch.physics_component);
+
+        if mover.latch.is_none() {
+            let mut input = BotInput::towards([couch[0] - position[0], couch[1] - position[1]]);
+            // Easy bots never grab, they only push
+            input.button_a = mover.touching_couch && difficulty != Difficulty::Easy;
+            return input;
+        }
+
+        let route = &self.level.route;
+        let distance = |p: &[f32; 2]| (p[0] - couch[0]).hypot(p[1] - couch[1]);
+        let nearest = (0..route.len())
+            .min_by(|&a, &b| distance(&route[a]).partial_cmp(&distance(&route[b])).unwrap_or(Ordering::Equal))
+            .unwrap_or(0);
+        let target = match route.get(nearest + 1) {
+            Some(next) => *next,
+            None => self.level.goal.center,
+        };
+
+        return BotInput::towards([target[0] - couch[0], target[1] - couch[1]]);
+    }
+
+    fn teardown(&mut self, physics: &mut PhysicsSystem) {
+        // Latches go with the bodies
+        physics.destroy_body(&self.walls.physics_component);
+        physics.destroy_body(&self.couch.physics_component);
+
+        for mover in self.movers.drain(..) {
+            physics.destroy_body(&mover.physics_component);
+        }
+    }
+}
@@ -0,0 +1,414 @@
+use std::collections::VecDeque;
+
+use game::minigame::{MiniGame, MiniGameSettings, Abandonment, Color, Point};
+use game::bot::{BotInput, Difficulty};
+use game::roster::{Roster, RosterPlayer, RosterEvent, PlayerId};
+use physics::{PhysicsSystem, PhysicsComponent, CollisionEvent, Category, CollisionFilter};
+use physics::{BodySpec, BodySpecs, BodyKind, ShapeSpec};
+use physics::spec::BODIES_FILE;
+use physics::{LAYER_PLAYER, LAYER_WALL, LAYER_SENSOR, LAYER_ALL};
+use draw::{DrawSystem, VertexComponent};
+use input::{InputSystem, ID};
+
+// Cells per side, the maze covers the whole arena from -1 to 1
+const SIZE: usize = 16;
+const CELL: f32 = 2.0 / SIZE as f32;
+
+// Seen from above, top row first. # wall, O hole, S start, G goal
+const MAZE: [&str; SIZE] = [
+    "################",
+    "#SSSS#.....#.O.#",
+    "#SSSS#.###.#.#.#",
+    "#....#...#...#.#",
+    "#.##.###.#####.#",
+    "#..#.....#.....#",
+    "##.###.#.#.#####",
+    "#...O#.#...#...#",
+    "#.####.###.#.#.#",
+    "#......#...#.#.#",
+    "######.#.###.#.#",
+    "#....#.#...#...#",
+    "#.##.#.###.#.#.#",
+    "#.O#...#.....#.#",
+    "#..#.###O#####G#",
+    "################",
+];
+
+const MARBLE_RADIUS: f32 = 0.035;
+// Marbles fall once their center is over the hole. The sensor reports any overlap with the
+// marble, so it is smaller than the hole by the marble's radius.
+const HOLE_RADIUS: f32 = 0.05;
+const HOLE_SENSOR_RADIUS: f32 = HOLE_RADIUS - MARBLE_RADIUS;
+
+// Gravity at full tilt, world units per second squared
+const MAX_GRAVITY: f32 = 1.0;
+// How much the tilt can change per tick, the board doesn't snap to the sticks
+const TILT_RATE: f32 = 0.05;
+
+const WALL_COLOR: Color = [0.5, 0.5, 0.5];
+const HOLE_COLOR: Color = [0.05, 0.05, 0.05];
+const GOAL_COLOR: Color = [0.9, 0.9, 0.9];
+
+type Cell = (usize, usize);
+
+struct Marble {
+    player_id: PlayerId,
+    name: String,
+    // None while the controller is disconnected, the marble is frozen until it's back
+    controller: Option<ID>,
+    start: Point,
+    draw_component: VertexComponent,
+    physics_component: PhysicsComponent,
+}
+
+// A static body with one fixture per cell, drawn like its shapes
+struct Fixed {
+    draw_component: VertexComponent,
+    physics_component: PhysicsComponent,
+}
+
+// Everyone tilts the same board: the average of all sticks is the direction gravity pulls.
+// Marbles that fall into a hole go back to their start, the first marble in the goal wins.
+pub struct MarbleMaze {
+    marbles: Vec<Marble>,
+    walls: Fixed,
+    holes: Fixed,
+    goal: Fixed,
+    starts: Vec<Point>,
+    // Steps from each cell to the goal around walls and holes, row major. For bots.
+    distances: Vec<Option<u32>>,
+    marble_spec: BodySpec,
+    tilt: [f32; 2],
+    winner: Option<PlayerId>,
+    abandonment: Abandonment,
+}
+
+impl MarbleMaze {
+    // The maze is fixed, the seed is unused
+    pub fn create(draw: &mut DrawSystem, physics: &mut PhysicsSystem, roster: &Roster, _settings: &MiniGameSettings, _seed: u64) -> Box<MiniGame> {
+        return Box::new(MarbleMaze::new(draw, physics, roster));
+    }
+
+    pub fn new(draw: &mut DrawSystem, physics: &mut PhysicsSystem, roster: &Roster) -> MarbleMaze {
+        let specs = BodySpecs::load(BODIES_FILE).expect("Failed to load body specs");
+
+        let mut wall_spec = specs.get("marble_wall");
+        let mut hole_spec = sensor_spec();
+        let mut goal_spec = sensor_spec();
+        let mut hole_shapes = vec![];
+        let mut starts = vec![];
+
+        for (cell, tile) in tiles() {
+            let center = cell_center(cell);
+            match tile {
+                '#' => {
+                    wall_spec.shapes.push(ShapeSpec::Box { width: CELL, height: CELL, center: [center[0], center[1]], angle: 0.0 });
+                }
+                'O' => {
+                    hole_spec.shapes.push(ShapeSpec::Circle { radius: HOLE_SENSOR_RADIUS, center: [center[0], center[1]] });
+                    hole_shapes.push(ShapeSpec::Circle { radius: HOLE_RADIUS, center: [center[0], center[1]] });
+                }
+                'G' => {
+                    goal_spec.shapes.push(ShapeSpec::Box { width: CELL * 0.5, height: CELL * 0.5, center: [center[0], center[1]], angle: 0.0 });
+                }
+                'S' => starts.push(center),
+                _ => {}
+            }
+        }
+
+        let walls = Fixed {
+            draw_component: draw.create_draw_object_shapes(&wall_spec.shapes, WALL_COLOR),
+            physics_component: physics.create_body(&wall_spec, Category::Wall, CollisionFilter::new(LAYER_WALL, LAYER_ALL)),
+        };
+        let holes = Fixed {
+            draw_component: draw.create_draw_object_shapes(&hole_shapes, HOLE_COLOR),
+            physics_component: physics.create_body(&hole_spec, Category::Boundary, CollisionFilter::new(LAYER_SENSOR, LAYER_PLAYER)),
+        };
+        let goal = Fixed {
+            draw_component: draw.create_draw_object_shapes(&goal_spec.shapes, GOAL_COLOR),
+            physics_component: physics.create_body(&goal_spec, Category::Boundary, CollisionFilter::new(LAYER_SENSOR, LAYER_PLAYER)),
+        };
+
+        let mut maze = MarbleMaze {
+            marbles: vec![],
+            walls: walls,
+            holes: holes,
+            goal: goal,
+            starts: starts,
+            distances: goal_distances(),
+            marble_spec: specs.get("marble"),
+            tilt: [0.0, 0.0],
+            winner: None,
+            abandonment: Abandonment::new(),
+        };
+
+        for roster_player in roster.players() {
+            maze.add_marble(draw, physics, roster_player);
+        }
+
+        return maze;
+    }
+
+    fn add_marble(&mut self, draw: &mut DrawSystem, physics: &mut PhysicsSystem, roster_player: &RosterPlayer) {
+        if self.marbles.iter().any(|m| m.player_id == roster_player.id) {
+            return;
+        }
+
+        // With more marbles than start cells they share cells, the overlap pushes them apart
+        let start = self.starts[self.marbles.len() % self.starts.len()];
+
+        let physics_object = physics.create_circle(MARBLE_RADIUS, &self.marble_spec, Category::Player, CollisionFilter::new(LAYER_PLAYER, LAYER_ALL));
+        physics.set_transform(&physics_object, start, 0.0);
+        if roster_player.controller.is_none() {
+            physics.set_active(&physics_object, false);
+        }
+
+        self.marbles.push(Marble {
+            player_id: roster_player.id,
+            name: roster_player.name.clone(),
+            controller: roster_player.controller,
+            start: start,
+            draw_component: draw.create_circle(&[0.0, 0.0, 0.0], MARBLE_RADIUS, roster_player.color),
+            physics_component: physics_object,
+        });
+        info!("New player added to marble maze");
+    }
+
+    fn set_controller(&mut self, physics: &mut PhysicsSystem, player_id: PlayerId, controller: Option<ID>) {
+        for marble in self.marbles.iter_mut().filter(|m| m.player_id == player_id) {
+            marble.controller = controller;
+            physics.set_active(&marble.physics_component, controller.is_some());
+        }
+    }
+
+    fn remove_marble(&mut self, physics: &mut PhysicsSystem, player_id: PlayerId) {
+        for marble in self.marbles.iter().filter(|m| m.player_id == player_id) {
+            physics.destroy_body(&marble.physics_component);
+        }
+        self.marbles.retain(|m| m.player_id != player_id);
+    }
+
+    // Ease the board towards the average stick of everyone playing
+    fn update_tilt(&mut self, physics: &mut PhysicsSystem, input: &InputSystem) {
+        let sticks: Vec<[f32; 2]> = self.marbles.iter()
+            .filter_map(|m| m.controller.and_then(|id| input.frame().controller(id)))
+            .map(|frame| [frame.left_stick[0], -frame.left_stick[1]])
+            .collect();
+
+        let mut target = [0.0, 0.0];
+        if !sticks.is_empty() {
+            for stick in sticks.iter() {
+                target = [target[0] + stick[0], target[1] + stick[1]];
+            }
+            target = [target[0] / sticks.len() as f32, target[1] / sticks.len() as f32];
+        }
+
+        let delta = [target[0] - self.tilt[0], target[1] - self.tilt[1]];
+        let length = delta[0].hypot(delta[1]);
+        let scale = if length > TILT_RATE { TILT_RATE / length } else { 1.0 };
+        self.tilt = [self.tilt[0] + delta[0] * scale, self.tilt[1] + delta[1] * scale];
+
+        physics.set_gravity([self.tilt[0] * MAX_GRAVITY, self.tilt[1] * MAX_GRAVITY, 0.0]);
+    }
+}
+
+impl MiniGame for MarbleMaze {
+    fn done(&self) -> bool {
+        return self.winner.is_some() || self.abandonment.expired();
+    }
+
+    fn step(&mut self, draw: &mut DrawSystem, physics: &mut PhysicsSystem, input: &mut InputSystem, roster: &mut Roster) {
+        for event in roster.events() {
+            match *event {
+                RosterEvent::Joined(id) => {
+                    if let Some(roster_player) = roster.player(id) {
+                        self.add_marble(draw, physics, roster_player);
+                    }
+                }
+                RosterEvent::Disconnected(id) => self.set_controller(physics, id, None),
+                RosterEvent::Reconnected(id) => {
+                    let controller = roster.player(id).and_then(|p| p.controller);
+                    self.set_controller(physics, id, controller);
+                }
+                RosterEvent::Left(id) => self.remove_marble(physics, id),
+            }
+        }
+
+        if self.winner.is_some() {
+            return;
+        }
+
+        if self.abandonment.paused(roster, physics.time_step()) {
+            return;
+        }
+
+        self.update_tilt(physics, input);
+
+        physics.step();
+
+        let holes = self.holes.physics_component.entity();
+        let goal = self.goal.physics_component.entity();
+        let mut fallen = vec![];
+        let mut arrived = vec![];
+        for collision in physics.collisions() {
+            match *collision {
+                CollisionEvent::Began { .. } => {}
+                _ => continue,
+            }
+
+            for (i, marble) in self.marbles.iter().enumerate() {
+                let entity = marble.physics_component.entity();
+                if collision.other(holes) == Some(entity) {
+                    fallen.push(i);
+                } else if collision.other(goal) == Some(entity) {
+                    arrived.push(i);
+                }
+            }
+        }
+
+        for i in fallen {
+            let marble = &self.marbles[i];
+            info!("{} fell into a hole", marble.name);
+            physics.set_transform(&marble.physics_component, marble.start, 0.0);
+            physics.set_velocity(&marble.physics_component, [0.0, 0.0, 0.0]);
+            if let Some(id) = marble.controller {
+                input.rumble(id, 0.8, 300);
+            }
+        }
+
+        // Marbles arriving in the same step finish in creation order
+        if let Some(&i) = arrived.first() {
+            info!("{} reached the goal", self.marbles[i].name);
+            self.winner = Some(self.marbles[i].player_id);
+        }
+
+        if let Some(id) = self.winner {
+            roster.add_score(id, 1);
+        }
+    }
+
+    fn render(&mut self, draw: &mut DrawSystem, physics: &PhysicsSystem, alpha: f32) {
+        for marble in self.marbles.iter_mut() {
+            marble.draw_component.transform.transform =
+                physics.get_interpolated_transformation(&marble.physics_component, alpha);
+        }
+
+        draw.draw(&mut self.holes.draw_component);
+        draw.draw(&mut self.goal.draw_component);
+        draw.draw(&mut self.walls.draw_component);
+
+        for marble in self.marbles.iter_mut() {
+            draw.draw(&mut marble.draw_component);
+        }
+    }
+
+    // Tilt towards the neighbouring cell closest to the goal. Bots share the board with
+    // everyone else, so they only pull their part.
+    fn bot_input(&self, controller: ID, difficulty: Difficulty, physics: &PhysicsSystem) -> BotInput {
+        let marble = match self.marbles.iter().filter(|m| m.controller == Some(controller)).next() {
+            Some(marble) => marble,
+            None => return BotInput::idle(),
+        };
+
+        let position = physics.position(&marble.physics_component);
+        let cell = match cell_at(position) {
+            Some(cell) => cell,
+            None => return BotInput::idle(),
+        };
+
+        let (row, col) = cell;
+        let neighbours = [(row - 1, col), (row + 1, col), (row, col - 1), (row, col + 1)];
+        let next = neighbours.iter()
+            .filter_map(|&n| self.distances[n.0 * SIZE + n.1].map(|d| (n, d)))
+            .min_by_key(|&(_, d)| d)
+            .map(|(n, _)| n);
+
+        let target = match next {
+            Some(next) => cell_center(next),
+            None => return BotInput::idle(),
+        };
+
+        // Better bots brake before they overshoot the turn
+        let anticipation = match difficulty {
+            Difficulty::Easy => 0.0,
+            Difficulty::Normal => 0.3,
+            Difficulty::Hard => 0.6,
+        };
+        let velocity = physics.velocity(&marble.physics_component);
+        return BotInput::towards([target[0] - position[0] - velocity[0] * anticipation,
+                                  target[1] - position[1] - velocity[1] * anticipation]);
+    }
+
+    fn teardown(&mut self, physics: &mut PhysicsSystem) {
+        physics.set_gravity([0.0, 0.0, 0.0]);
+
+        physics.destroy_body(&self.walls.physics_component);
+        physics.destroy_body(&self.holes.physics_component);
+        physics.destroy_body(&self.goal.physics_component);
+
+        for marble in self.marbles.drain(..) {
+            physics.destroy_body(&marble.physics_component);
+        }
+    }
+}
+
+fn sensor_spec() -> BodySpec {
+    let mut spec = BodySpec::default();
+    spec.body_type = BodyKind::Static;
+    spec.sensor = true;
+    return spec;
+}
+
+// Every cell of the maze with its tile, as (row, column)
+fn tiles() -> Vec<(Cell, char)> {
+    let mut tiles = vec![];
+    for (row, line) in MAZE.iter().enumerate() {
+        for (col, tile) in line.chars().enumerate() {
+            tiles.push(((row, col), tile));
+        }
+    }
+    return tiles;
+}
+
+fn cell_center(cell: Cell) -> Point {
+    let (row, col) = cell;
+    return [-1.0 + (col as f32 + 0.5) * CELL, 1.0 - (row as f32 + 0.5) * CELL, 0.0];
+}
+
+// Cell under a point, None outside the board's border walls
+fn cell_at(point: Point) -> Option<Cell> {
+    let col = ((point[0] + 1.0) / CELL).floor();
+    let row = ((1.0 - point[1]) / CELL).floor();
+    if col < 1.0 || row < 1.0 || col >= (SIZE - 1) as f32 || row >= (SIZE - 1) as f32 {
+        return None;
+    }
+    return Some((row as usize, col as usize));
+}
+
+// Breadth first from the goal, walls and holes are never entered
+fn goal_distances() -> Vec<Option<u32>> {
+    let blocked: Vec<bool> = tiles().iter().map(|&(_, tile)| tile == '#' || tile == 'O').collect();
+    let mut distances = vec![None; blocked.len()];
+    let mut queue = VecDeque::new();
+
+    for (cell, tile) in tiles() {
+        if tile == 'G' {
+            distances[cell.0 * SIZE + cell.1] = Some(0);
+            queue.push_back(cell);
+        }
+    }
+
+    while let Some((row, col)) = queue.pop_front() {
+        let distance = distances[row * SIZE + col].unwrap_or(0);
+        // The border is all wall, so neighbours of open cells are always on the board
+        for &(r, c) in [(row - 1, col), (row + 1, col), (row, col - 1), (row, col + 1)].iter() {
+            let index = r * SIZE + c;
+            if !blocked[index] && distances[index].is_none() {
+                distances[index] = Some(distance + 1);
+                queue.push_back((r, c));
+            }
+        }
+    }
+
+    return distances;
+}
@@ -1,4 +1,9 @@
 // pub mod box2d;
+pub mod avoid_the_wall;
+pub mod couch_maze;
+pub mod marble_maze;
+pub mod racing;
+pub mod snake;
 pub mod sumo;
 // pub mod triangle_buffers;
 // pub mod triangle_shader;
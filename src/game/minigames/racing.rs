@@ -0,0 +1,447 @@
+use std::f32::consts::PI;
+
+use sdl2::controller::Button;
+
+use game::minigame::{MiniGame, MiniGameSettings, Abandonment, Color, Point};
+use game::bot::{BotInput, Difficulty};
+use game::roster::{Roster, RosterPlayer, RosterEvent, PlayerId};
+use physics::{PhysicsSystem, PhysicsComponent, CollisionEvent, Category, CollisionFilter};
+use physics::{BodySpec, BodySpecs, BodyKind, ShapeSpec};
+use physics::spec::BODIES_FILE;
+use physics::{LAYER_PLAYER, LAYER_WALL, LAYER_SENSOR, LAYER_ALL};
+use draw::{DrawSystem, VertexComponent, TextComponent, IDENTITY};
+use input::{InputSystem, ID};
+
+const LAPS: u32 = 3;
+
+// Ticks the others get to finish once the winner crossed the line
+const FINISH_GRACE_TICKS: u32 = 600;
+
+// World units, the track runs counter clockwise between the outer and the inner wall
+const OUTER: [f32; 2] = [0.95, 0.85];
+const OUTER_CHAMFER: f32 = 0.25;
+const INNER: [f32; 2] = [0.5, 0.35];
+const INNER_CHAMFER: f32 = 0.1;
+const WALL_WIDTH: f32 = 0.01;
+
+// Middle of the track at the checkpoints (even) and the corners (odd), in driving order.
+// Checkpoint 0 is the start and finish line.
+const WAYPOINTS: [[f32; 2]; 8] = [
+    [0.0, -0.6], [0.725, -0.6],
+    [0.725, 0.0], [0.725, 0.6],
+    [0.0, 0.6], [-0.725, 0.6],
+    [-0.725, 0.0], [-0.725, -0.6],
+];
+
+// Pushing forward and backward, world units like apply_force_to_center
+const ENGINE_FORCE: f32 = 0.3;
+const REVERSE_FORCE: f32 = 0.15;
+// Radians per second at full steering, reached at TURN_FULL_SPEED and above
+const TURN_SPEED: f32 = 4.0;
+const TURN_FULL_SPEED: f32 = 0.5;
+// Share of the sideways velocity removed each tick, 1 drives on rails
+const GRIP: f32 = 0.9;
+
+const WALL_COLOR: Color = [0.5, 0.5, 0.5];
+const LINE_COLOR: Color = [0.9, 0.9, 0.9];
+
+struct Car {
+    player_id: PlayerId,
+    name: String,
+    // None while the controller is disconnected, the car is parked until it's back
+    controller: Option<ID>,
+    // Index of the checkpoint the car has to cross next
+    next_checkpoint: usize,
+    laps: u32,
+    // Place in the finish order, from 0
+    place: Option<usize>,
+    draw_component: VertexComponent,
+    lap_text: TextComponent,
+    physics_component: PhysicsComponent,
+}
+
+struct Checkpoint {
+    physics_component: PhysicsComponent,
+}
+
+struct Wall {
+    draw_component: VertexComponent,
+    physics_component: PhysicsComponent,
+}
+
+pub struct Racing {
+    cars: Vec<Car>,
+    walls: Vec<Wall>,
+    checkpoints: Vec<Checkpoint>,
+    finish_line: VertexComponent,
+    car_spec: BodySpec,
+    // Player ids in the order they finished
+    finished: Vec<PlayerId>,
+    // Ticks since the first car finished
+    grace: Option<u32>,
+    over: bool,
+    abandonment: Abandonment,
+}
+
+impl Racing {
+    // Racing has no randomness, the seed is unused
+    pub fn create(draw: &mut DrawSystem, physics: &mut PhysicsSystem, roster: &Roster, _settings: &MiniGameSettings, _seed: u64) -> Box<MiniGame> {
+        return Box::new(Racing::new(draw, physics, roster));
+    }
+
+    pub fn new(draw: &mut DrawSystem, physics: &mut PhysicsSystem, roster: &Roster) -> Racing {
+        let specs = BodySpecs::load(BODIES_FILE).expect("Failed to load body specs");
+        let wall_spec = specs.get("racing_wall");
+
+        let walls = vec![
+            Racing::new_wall(draw, physics, chamfered(OUTER, OUTER_CHAMFER), &wall_spec),
+            Racing::new_wall(draw, physics, chamfered(INNER, INNER_CHAMFER), &wall_spec),
+        ];
+
+        let checkpoints = (0..WAYPOINTS.len() / 2)
+            .map(|i| Racing::new_checkpoint(physics, i))
+            .collect();
+
+        let finish_line = draw.create_draw_object_shapes(&vec![checkpoint_shape(0)], LINE_COLOR);
+
+        let mut racing = Racing {
+            cars: vec![],
+            walls: walls,
+            checkpoints: checkpoints,
+            finish_line: finish_line,
+            car_spec: specs.get("racing_car"),
+            finished: vec![],
+            grace: None,
+            over: false,
+            abandonment: Abandonment::new(),
+        };
+
+        for roster_player in roster.players() {
+            racing.add_car(draw, physics, roster_player);
+        }
+
+        return racing;
+    }
+
+    fn new_wall(draw: &mut DrawSystem, physics: &mut PhysicsSystem, vertices: Vec<Point>, spec: &BodySpec) -> Wall {
+        let draw_object = draw.create_line(&vertices, true, WALL_WIDTH, WALL_COLOR);
+        let physics_object = physics.create_chain(&vertices, true, spec, Category::Wall, CollisionFilter::new(LAYER_WALL, LAYER_ALL));
+
+        Wall {
+            draw_component: draw_object,
+            physics_component: physics_object,
+        }
+    }
+
+    // Sensor across the track, only cars trigger it
+    fn new_checkpoint(physics: &mut PhysicsSystem, index: usize) -> Checkpoint {
+        let mut spec = BodySpec::default();
+        spec.body_type = BodyKind::Static;
+        spec.sensor = true;
+        let spec = spec.with_shape(checkpoint_shape(index));
+
+        Checkpoint {
+            physics_component: physics.create_body(&spec, Category::Boundary, CollisionFilter::new(LAYER_SENSOR, LAYER_PLAYER)),
+        }
+    }
+
+    fn add_car(&mut self, draw: &mut DrawSystem, physics: &mut PhysicsSystem, roster_player: &RosterPlayer) {
+        if self.cars.iter().any(|c| c.player_id == roster_player.id) {
+            return;
+        }
+
+        let physics_object = physics.create_body(&self.car_spec, Category::Player, CollisionFilter::new(LAYER_PLAYER, LAYER_ALL));
+        // Facing right, just past the start line so the first crossing completes a lap
+        physics.set_transform(&physics_object, grid_position(self.cars.len()), -PI / 2.0);
+        if roster_player.controller.is_none() {
+            physics.set_active(&physics_object, false);
+        }
+
+        let mut lap_text = draw.create_text();
+        lap_text.color = roster_player.color;
+
+        self.cars.push(Car {
+            player_id: roster_player.id,
+            name: roster_player.name.clone(),
+            controller: roster_player.controller,
+            next_checkpoint: 1,
+            laps: 0,
+            place: None,
+            draw_component: draw.create_draw_object_shapes(&self.car_spec.shapes, roster_player.color),
+            lap_text: lap_text,
+            physics_component: physics_object,
+        });
+        info!("New player added to race");
+    }
+
+    fn set_controller(&mut self, physics: &mut PhysicsSystem, player_id: PlayerId, controller: Option<ID>) {
+        for car in self.cars.iter_mut().filter(|c| c.player_id == player_id) {
+            car.controller = controller;
+            physics.set_active(&car.physics_component, controller.is_some());
+        }
+    }
+
+    fn remove_car(&mut self, physics: &mut PhysicsSystem, player_id: PlayerId) {
+        for car in self.cars.iter().filter(|c| c.player_id == player_id) {
+            physics.destroy_body(&car.physics_component);
+        }
+        self.cars.retain(|c| c.player_id != player_id);
+    }
+
+    // Throttle on the right trigger or A, brake and reverse on the left trigger or B, steer with the stick
+    fn drive(&self, physics: &PhysicsSystem, input: &InputSystem) {
+        for car in self.cars.iter().filter(|c| c.place.is_none()) {
+            let frame = match car.controller.and_then(|id| input.frame().controller(id)) {
+                Some(frame) => *frame,
+                None => continue,
+            };
+
+            let throttle = frame.right_trigger.max(if frame.is_down(Button::A) { 1.0 } else { 0.0 });
+            let brake = frame.left_trigger.max(if frame.is_down(Button::B) { 1.0 } else { 0.0 });
+            let steer = frame.left_stick[0];
+
+            let angle = physics.angle(&car.physics_component);
+            let forward = [-angle.sin(), angle.cos()];
+            let right = [angle.cos(), angle.sin()];
+            let velocity = physics.velocity(&car.physics_component);
+            let mass = physics.mass(&car.physics_component);
+
+            // Tyres don't slide sideways
+            let lateral = velocity[0] * right[0] + velocity[1] * right[1];
+            physics.apply_impulse_to_center([-right[0] * lateral * mass * GRIP, -right[1] * lateral * mass * GRIP, 0.0],
+                                            &car.physics_component);
+
+            let push = throttle * ENGINE_FORCE - brake * REVERSE_FORCE;
+            physics.apply_force_to_center([forward[0] * push, forward[1] * push, 0.0], &car.physics_component);
+
+            // Cars only turn while rolling, and the other way round when reversing
+            let speed = velocity[0] * forward[0] + velocity[1] * forward[1];
+            let rolling = (speed / TURN_FULL_SPEED).max(-1.0).min(1.0);
+            physics.set_angular_velocity(&car.physics_component, -steer * TURN_SPEED * rolling);
+        }
+    }
+
+    // Checkpoints only count in order, a lap is done when the start line is crossed after all others
+    fn cross_checkpoints(&mut self, physics: &PhysicsSystem, input: &mut InputSystem) {
+        for collision in physics.collisions() {
+            match *collision {
+                CollisionEvent::Began { .. } => {}
+                _ => continue,
+            }
+
+            for (index, checkpoint) in self.checkpoints.iter().enumerate() {
+                let entity = match collision.other(checkpoint.physics_component.entity()) {
+                    Some(entity) => entity,
+                    None => continue,
+                };
+
+                for car in self.cars.iter_mut() {
+                    if car.place.is_some() || car.physics_component.entity() != entity || car.next_checkpoint != index {
+                        continue;
+                    }
+
+                    car.next_checkpoint = (index + 1) % self.checkpoints.len();
+                    if index != 0 {
+                        continue;
+                    }
+
+                    car.laps = car.laps + 1;
+                    if car.laps >= LAPS {
+                        car.place = Some(self.finished.len());
+                        self.finished.push(car.player_id);
+                        info!("{} finished the race in place {}", car.name, self.finished.len());
+                        if let Some(id) = car.controller {
+                            input.rumble(id, 1.0, 400);
+                        }
+                    } else if let Some(id) = car.controller {
+                        input.rumble(id, 0.5, 150);
+                    }
+                }
+            }
+        }
+    }
+
+    // Everyone still connected finished, or the others ran out of time
+    fn race_over(&self) -> bool {
+        if self.cars.is_empty() {
+            return false;
+        }
+        let racing = self.cars.iter().filter(|c| c.controller.is_some() && c.place.is_none()).count();
+        let out_of_time = self.grace.map_or(false, |ticks| ticks >= FINISH_GRACE_TICKS);
+        return racing == 0 || out_of_time;
+    }
+}
+
+impl MiniGame for Racing {
+    fn done(&self) -> bool {
+        return self.over || self.abandonment.expired();
+    }
+
+    fn step(&mut self, draw: &mut DrawSystem, physics: &mut PhysicsSystem, input: &mut InputSystem, roster: &mut Roster) {
+        for event in roster.events() {
+            match *event {
+                RosterEvent::Joined(id) => {
+                    if let Some(roster_player) = roster.player(id) {
+                        self.add_car(draw, physics, roster_player);
+                    }
+                }
+                RosterEvent::Disconnected(id) => self.set_controller(physics, id, None),
+                RosterEvent::Reconnected(id) => {
+                    let controller = roster.player(id).and_then(|p| p.controller);
+                    self.set_controller(physics, id, controller);
+                }
+                RosterEvent::Left(id) => self.remove_car(physics, id),
+            }
+        }
+
+        if self.over {
+            return;
+        }
+
+        if self.abandonment.paused(roster, physics.time_step()) {
+            return;
+        }
+
+        self.drive(physics, input);
+
+        physics.step();
+
+        self.cross_checkpoints(physics, input);
+
+        if !self.finished.is_empty() {
+            self.grace = Some(self.grace.map_or(0, |ticks| ticks + 1));
+        }
+
+        // Points by finish order, cars that didn't finish get none
+        if self.race_over() {
+            let count = self.cars.len();
+            for (place, &id) in self.finished.iter().enumerate() {
+                roster.add_score(id, (count - place) as u64);
+            }
+            self.over = true;
+        }
+
+        for (i, car) in self.cars.iter_mut().enumerate() {
+            car.lap_text.text = match car.place {
+                Some(place) => format!("{}{}", place + 1, ordinal_suffix(place + 1)),
+                None => format!("{}/{}", (car.laps + 1).min(LAPS), LAPS),
+            };
+            let mut transform = IDENTITY;
+            transform.transform[3][0] = -0.95 + i as f32 * 0.24;
+            transform.transform[3][1] = 0.9;
+            car.lap_text.transform = transform;
+        }
+    }
+
+    fn render(&mut self, draw: &mut DrawSystem, physics: &PhysicsSystem, alpha: f32) {
+        for car in self.cars.iter_mut() {
+            car.draw_component.transform.transform =
+                physics.get_interpolated_transformation(&car.physics_component, alpha);
+        }
+
+        draw.draw(&mut self.finish_line);
+
+        for wall in self.walls.iter_mut() {
+            draw.draw(&mut wall.draw_component);
+        }
+
+        for car in self.cars.iter_mut() {
+            draw.draw(&mut car.draw_component);
+            draw.draw(&mut car.lap_text);
+        }
+    }
+
+    // Follow the middle of the track: aim for the corner before the next checkpoint, then the checkpoint
+    fn bot_input(&self, controller: ID, difficulty: Difficulty, physics: &PhysicsSystem) -> BotInput {
+        let car = match self.cars.iter().filter(|c| c.place.is_none() && c.controller == Some(controller)).next() {
+            Some(car) => car,
+            None => return BotInput::idle(),
+        };
+
+        let position = physics.position(&car.physics_component);
+        let checkpoint = WAYPOINTS[car.next_checkpoint * 2];
+        let corner = WAYPOINTS[(car.next_checkpoint * 2 + WAYPOINTS.len() - 1) % WAYPOINTS.len()];
+
+        // Better bots cut the corners earlier
+        let cut = match difficulty {
+            Difficulty::Easy => 0.05,
+            Difficulty::Normal => 0.15,
+            Difficulty::Hard => 0.25,
+        };
+        let distance = |a: [f32; 2], b: [f32; 2]| (a[0] - b[0]).hypot(a[1] - b[1]);
+        let target = match distance([position[0], position[1]], checkpoint) > distance(corner, checkpoint) + cut {
+            true => corner,
+            false => checkpoint,
+        };
+
+        let angle = physics.angle(&car.physics_component);
+        let forward = [-angle.sin(), angle.cos()];
+        let to_target = [target[0] - position[0], target[1] - position[1]];
+        // Positive when the target is on the left
+        let cross = forward[0] * to_target[1] - forward[1] * to_target[0];
+        let dot = forward[0] * to_target[0] + forward[1] * to_target[1];
+        let steer = (-cross.atan2(dot) * 2.0).max(-1.0).min(1.0);
+
+        let mut input = BotInput::idle();
+        input.stick = [steer, 0.0];
+        // Let go of the throttle in sharp turns
+        input.button_a = steer.abs() < 0.9;
+        return input;
+    }
+
+    fn teardown(&mut self, physics: &mut PhysicsSystem) {
+        for wall in self.walls.iter() {
+            physics.destroy_body(&wall.physics_component);
+        }
+
+        for checkpoint in self.checkpoints.iter() {
+            physics.destroy_body(&checkpoint.physics_component);
+        }
+
+        for car in self.cars.drain(..) {
+            physics.destroy_body(&car.physics_component);
+        }
+    }
+}
+
+// Rectangle around the origin with cut corners, counter clockwise
+fn chamfered(half: [f32; 2], chamfer: f32) -> Vec<Point> {
+    let (x, y) = (half[0], half[1]);
+    return vec![
+        [x - chamfer, -y, 0.0],
+        [x, -y + chamfer, 0.0],
+        [x, y - chamfer, 0.0],
+        [x - chamfer, y, 0.0],
+        [-x + chamfer, y, 0.0],
+        [-x, y - chamfer, 0.0],
+        [-x, -y + chamfer, 0.0],
+        [-x + chamfer, -y, 0.0],
+    ];
+}
+
+// Thin box across the track at the checkpoint's waypoint
+fn checkpoint_shape(index: usize) -> ShapeSpec {
+    let center = WAYPOINTS[index * 2];
+    // Checkpoints on the bottom and top straights are vertical lines
+    let (width, height) = match index % 2 {
+        0 => (0.02, OUTER[1] - INNER[1]),
+        _ => (OUTER[0] - INNER[0], 0.02),
+    };
+    return ShapeSpec::Box { width: width, height: height, center: center, angle: 0.0 };
+}
+
+// Two rows of four across the bottom straight, right after the start line
+fn grid_position(n: usize) -> Point {
+    let row = (n / 4) as f32;
+    let lane = (n % 4) as f32;
+    return [0.06 + row * 0.1, -0.78 + lane * 0.12, 0.0];
+}
+
+fn ordinal_suffix(place: usize) -> &'static str {
+    return match place {
+        1 => "st",
+        2 => "nd",
+        3 => "rd",
+        _ => "th",
+    };
+}
@@ -0,0 +1,438 @@
+use std::collections::VecDeque;
+
+use sdl2::controller::Button;
+
+use game::minigame::{MiniGame, MiniGameSettings, Abandonment, Color};
+use game::bot::{BotInput, Difficulty};
+use game::roster::{Roster, RosterPlayer, RosterEvent, PlayerId};
+use physics::{PhysicsSystem, ShapeSpec};
+use draw::{DrawSystem, DrawComponent, VertexComponent, TextComponent, IDENTITY};
+use input::{InputSystem, ID};
+use rng::Rng;
+
+// Cells per side, the grid covers the whole arena from -1 to 1
+const GRID: i32 = 32;
+const CELL: f32 = 2.0 / GRID as f32;
+// Ticks between two moves
+const MOVE_TICKS: u32 = 6;
+const START_LENGTH: usize = 4;
+
+const WALL_COLOR: Color = [0.5, 0.5, 0.5];
+const FOOD_COLOR: Color = [0.9, 0.9, 0.9];
+const DEAD_COLOR: Color = [0.05, 0.05, 0.05];
+
+type Cell = (i32, i32);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl Direction {
+    fn offset(&self) -> Cell {
+        match *self {
+            Direction::Up => (0, 1),
+            Direction::Down => (0, -1),
+            Direction::Left => (-1, 0),
+            Direction::Right => (1, 0),
+        }
+    }
+
+    fn opposite(&self) -> Direction {
+        match *self {
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+            Direction::Left => Direction::Right,
+            Direction::Right => Direction::Left,
+        }
+    }
+
+    // Stick with y down like SDL, the dominant axis wins
+    fn from_stick(stick: &[f32; 2]) -> Option<Direction> {
+        let (x, y) = (stick[0], stick[1]);
+        if x.abs() < 0.5 && y.abs() < 0.5 {
+            return None;
+        }
+        if x.abs() > y.abs() {
+            return Some(if x > 0.0 { Direction::Right } else { Direction::Left });
+        }
+        return Some(if y > 0.0 { Direction::Down } else { Direction::Up });
+    }
+}
+
+struct Player {
+    player_id: PlayerId,
+    name: String,
+    color: Color,
+    // None while the controller is disconnected, the snake stops until it's back
+    controller: Option<ID>,
+    alive: bool,
+    // Head first
+    cells: VecDeque<Cell>,
+    direction: Direction,
+    // Turn to take at the next move
+    next_direction: Direction,
+    segments: VecDeque<VertexComponent>,
+    length_text: TextComponent,
+}
+
+pub struct Snake {
+    snakes: Vec<Player>,
+    food: Vec<(Cell, VertexComponent)>,
+    walls: Vec<VertexComponent>,
+    ticks: u32,
+    rng: Rng,
+    // Set once the round is over
+    winner: Option<Option<PlayerId>>,
+    abandonment: Abandonment,
+}
+
+impl Snake {
+    pub fn create(draw: &mut DrawSystem, _physics: &mut PhysicsSystem, roster: &Roster, _settings: &MiniGameSettings, seed: u64) -> Box<MiniGame> {
+        return Box::new(Snake::new(draw, roster, seed));
+    }
+
+    pub fn new(draw: &mut DrawSystem, roster: &Roster, seed: u64) -> Snake {
+        let mut walls = vec![];
+        for &(center, width, height) in [([0.0, 1.0], 2.0 + CELL, CELL),
+                                         ([0.0, -1.0], 2.0 + CELL, CELL),
+                                         ([1.0, 0.0], CELL, 2.0 + CELL),
+                                         ([-1.0, 0.0], CELL, 2.0 + CELL)].iter() {
+            let shape = ShapeSpec::Box { width: width, height: height, center: center, angle: 0.0 };
+            walls.push(draw.create_draw_object_shapes(&vec![shape], WALL_COLOR));
+        }
+
+        let mut game = Snake {
+            snakes: vec![],
+            food: vec![],
+            walls: walls,
+            ticks: 0,
+            rng: Rng::new(seed),
+            winner: None,
+            abandonment: Abandonment::new(),
+        };
+
+        for roster_player in roster.players() {
+            game.add_snake(draw, roster_player);
+        }
+        game.refill_food(draw);
+
+        return game;
+    }
+
+    fn add_snake(&mut self, draw: &mut DrawSystem, roster_player: &RosterPlayer) {
+        if self.snakes.iter().any(|s| s.player_id == roster_player.id) || self.snakes.len() >= 8 {
+            return;
+        }
+
+        let (head, direction) = start(self.snakes.len());
+        let offset = direction.opposite().offset();
+
+        let mut snake = Player {
+            player_id: roster_player.id,
+            name: roster_player.name.clone(),
+            color: roster_player.color,
+            controller: roster_player.controller,
+            alive: true,
+            cells: VecDeque::new(),
+            direction: direction,
+            next_direction: direction,
+            segments: VecDeque::new(),
+            length_text: draw.create_text(),
+        };
+
+        for i in 0..START_LENGTH as i32 {
+            snake.cells.push_back((head.0 + offset.0 * i, head.1 + offset.1 * i));
+            snake.segments.push_back(create_segment(draw, snake.color));
+        }
+        snake.length_text.color = snake.color;
+
+        self.snakes.push(snake);
+    }
+
+    // One food per two snakes, at least one
+    fn refill_food(&mut self, draw: &mut DrawSystem) {
+        let wanted = 1 + self.snakes.len() / 2;
+        while self.food.len() < wanted {
+            let free = self.free_cells();
+            if free.is_empty() {
+                return;
+            }
+            let cell = free[self.rng.below(free.len() as u32) as usize];
+            let mut component = create_segment(draw, FOOD_COLOR);
+            place(&mut component, cell);
+            self.food.push((cell, component));
+        }
+    }
+
+    fn free_cells(&self) -> Vec<Cell> {
+        let mut free = vec![];
+        for x in 0..GRID {
+            for y in 0..GRID {
+                let cell = (x, y);
+                if !self.occupied(cell) && !self.food.iter().any(|f| f.0 == cell) {
+                    free.push(cell);
+                }
+            }
+        }
+        return free;
+    }
+
+    // Crashed snakes stay on the field as obstacles
+    fn occupied(&self, cell: Cell) -> bool {
+        return self.snakes.iter().any(|s| s.cells.contains(&cell));
+    }
+
+    fn read_input(&mut self, input: &InputSystem) {
+        for snake in self.snakes.iter_mut().filter(|s| s.alive) {
+            let frame = match snake.controller.and_then(|id| input.frame().controller(id)) {
+                Some(frame) => *frame,
+                None => continue,
+            };
+
+            let pad = [(Button::DPadUp, Direction::Up),
+                       (Button::DPadDown, Direction::Down),
+                       (Button::DPadLeft, Direction::Left),
+                       (Button::DPadRight, Direction::Right)];
+            let wanted = pad.iter()
+                .filter(|&&(button, _)| frame.is_down(button))
+                .map(|&(_, direction)| direction)
+                .next()
+                .or(Direction::from_stick(&frame.left_stick));
+
+            // Turning back onto the own neck is never allowed
+            if let Some(direction) = wanted {
+                if direction != snake.direction.opposite() {
+                    snake.next_direction = direction;
+                }
+            }
+        }
+    }
+
+    // Move every snake one cell. Snakes die by leaving the grid or running into a body,
+    // snakes meeting head on both die.
+    fn advance(&mut self, draw: &mut DrawSystem) {
+        let moving: Vec<usize> = (0..self.snakes.len())
+            .filter(|&i| self.snakes[i].alive && self.snakes[i].controller.is_some())
+            .collect();
+
+        let mut heads = vec![];
+        for &i in moving.iter() {
+            let snake = &mut self.snakes[i];
+            snake.direction = snake.next_direction;
+            let offset = snake.direction.offset();
+            let head = snake.cells[0];
+            heads.push((i, (head.0 + offset.0, head.1 + offset.1)));
+        }
+
+        // Tails move out of the way first, unless the snake is eating
+        let mut eating = vec![];
+        for &(i, head) in heads.iter() {
+            if let Some(food) = self.food.iter().position(|f| f.0 == head) {
+                self.food.remove(food);
+                eating.push(i);
+            } else {
+                self.snakes[i].cells.pop_back();
+            }
+        }
+
+        let mut dying = vec![];
+        for &(i, head) in heads.iter() {
+            let outside = head.0 < 0 || head.1 < 0 || head.0 >= GRID || head.1 >= GRID;
+            let head_on = heads.iter().any(|&(j, other)| j != i && other == head);
+            if outside || head_on || self.occupied(head) {
+                dying.push(i);
+            }
+        }
+
+        for &(i, head) in heads.iter() {
+            let snake = &mut self.snakes[i];
+            if dying.contains(&i) {
+                snake.alive = false;
+                for segment in snake.segments.iter_mut() {
+                    segment.set_color(DEAD_COLOR);
+                }
+                info!("{} crashed", snake.name);
+                continue;
+            }
+
+            snake.cells.push_front(head);
+            // Reuse the tail segment for the new head, growing snakes get a new one
+            let segment = match eating.contains(&i) {
+                true => create_segment(draw, snake.color),
+                false => snake.segments.pop_back().unwrap_or_else(|| create_segment(draw, snake.color)),
+            };
+            snake.segments.push_front(segment);
+        }
+
+        // Snakes that crashed without eating lost their tail, keep the drawing in sync
+        for snake in self.snakes.iter_mut() {
+            while snake.segments.len() > snake.cells.len() {
+                snake.segments.pop_back();
+            }
+        }
+
+        self.refill_food(draw);
+    }
+
+    fn round_over(&self) -> bool {
+        let alive = self.snakes.iter().filter(|s| s.alive).count();
+        return match self.snakes.len() {
+            0 => false,
+            1 => alive == 0,
+            _ => alive <= 1,
+        };
+    }
+}
+
+impl MiniGame for Snake {
+    fn done(&self) -> bool {
+        return self.winner.is_some() || self.abandonment.expired();
+    }
+
+    fn step(&mut self, draw: &mut DrawSystem, physics: &mut PhysicsSystem, input: &mut InputSystem, roster: &mut Roster) {
+        for event in roster.events() {
+            match *event {
+                RosterEvent::Joined(id) => {
+                    if let Some(roster_player) = roster.player(id) {
+                        self.add_snake(draw, roster_player);
+                    }
+                }
+                RosterEvent::Disconnected(id) | RosterEvent::Reconnected(id) => {
+                    let controller = roster.player(id).and_then(|p| p.controller);
+                    for snake in self.snakes.iter_mut().filter(|s| s.player_id == id) {
+                        snake.controller = controller;
+                    }
+                }
+                RosterEvent::Left(id) => self.snakes.retain(|s| s.player_id != id),
+            }
+        }
+
+        if self.winner.is_some() {
+            return;
+        }
+
+        if self.abandonment.paused(roster, physics.time_step()) {
+            return;
+        }
+
+        self.read_input(input);
+
+        self.ticks = self.ticks + 1;
+        if self.ticks % MOVE_TICKS == 0 {
+            self.advance(draw);
+        }
+
+        if self.round_over() {
+            let winner = self.snakes.iter().filter(|s| s.alive).map(|s| s.player_id).next();
+            if let Some(id) = winner {
+                if let Some(snake) = self.snakes.iter().filter(|s| s.player_id == id).next() {
+                    info!("{} wins snake with length {}", snake.name, snake.cells.len());
+                }
+                roster.add_score(id, 1);
+            }
+            self.winner = Some(winner);
+        }
+
+        for (i, snake) in self.snakes.iter_mut().enumerate() {
+            snake.length_text.text = format!("{}", snake.cells.len());
+            let mut transform = IDENTITY;
+            transform.transform[3][0] = -0.95 + i as f32 * 0.24;
+            transform.transform[3][1] = 0.9;
+            snake.length_text.transform = transform;
+        }
+    }
+
+    fn render(&mut self, draw: &mut DrawSystem, _physics: &PhysicsSystem, _alpha: f32) {
+        for wall in self.walls.iter_mut() {
+            draw.draw(wall);
+        }
+        for &mut (_, ref mut food) in self.food.iter_mut() {
+            draw.draw(food);
+        }
+        for snake in self.snakes.iter_mut() {
+            for (cell, segment) in snake.cells.iter().zip(snake.segments.iter_mut()) {
+                place(segment, *cell);
+                draw.draw(segment);
+            }
+            draw.draw(&mut snake.length_text);
+        }
+    }
+
+    // Head for the closest food, taking the first turn that doesn't crash
+    fn bot_input(&self, controller: ID, difficulty: Difficulty, _physics: &PhysicsSystem) -> BotInput {
+        let snake = match self.snakes.iter().filter(|s| s.alive && s.controller == Some(controller)).next() {
+            Some(snake) => snake,
+            None => return BotInput::idle(),
+        };
+        let head = snake.cells[0];
+
+        // Easy bots only look at the closest food when it's near
+        let sight = match difficulty {
+            Difficulty::Easy => GRID / 4,
+            Difficulty::Normal => GRID / 2,
+            Difficulty::Hard => GRID * 2,
+        };
+        let target = self.food.iter()
+            .map(|f| f.0)
+            .filter(|f| (f.0 - head.0).abs() + (f.1 - head.1).abs() <= sight)
+            .min_by_key(|f| (f.0 - head.0).abs() + (f.1 - head.1).abs());
+
+        let mut options = vec![snake.direction];
+        for &direction in [Direction::Up, Direction::Down, Direction::Left, Direction::Right].iter() {
+            if direction != snake.direction && direction != snake.direction.opposite() {
+                options.push(direction);
+            }
+        }
+
+        let safe = |direction: &Direction| {
+            let offset = direction.offset();
+            let cell = (head.0 + offset.0, head.1 + offset.1);
+            cell.0 >= 0 && cell.1 >= 0 && cell.0 < GRID && cell.1 < GRID && !self.occupied(cell)
+        };
+        let distance = |direction: &Direction| match target {
+            Some(food) => {
+                let offset = direction.offset();
+                (food.0 - head.0 - offset.0).abs() + (food.1 - head.1 - offset.1).abs()
+            }
+            None => 0,
+        };
+
+        let choice = options.iter()
+            .filter(|d| safe(*d))
+            .min_by_key(|d| distance(*d))
+            .cloned()
+            .unwrap_or(snake.direction);
+
+        let offset = choice.offset();
+        return BotInput::towards([offset.0 as f32, offset.1 as f32]);
+    }
+
+    fn teardown(&mut self, _physics: &mut PhysicsSystem) {}
+}
+
+// Start cell and heading of the n-th snake: two per side, heading inwards
+fn start(n: usize) -> (Cell, Direction) {
+    let lane = if n / 4 == 0 { GRID / 3 } else { GRID * 2 / 3 };
+    return match n % 4 {
+        0 => ((START_LENGTH as i32, lane), Direction::Right),
+        1 => ((GRID - 1 - START_LENGTH as i32, GRID - 1 - lane), Direction::Left),
+        2 => ((GRID - 1 - lane, START_LENGTH as i32), Direction::Up),
+        _ => ((lane, GRID - 1 - START_LENGTH as i32), Direction::Down),
+    };
+}
+
+fn create_segment(draw: &mut DrawSystem, color: Color) -> VertexComponent {
+    // Slightly smaller than a cell so segments stay apart
+    let size = CELL * 0.9;
+    let shape = ShapeSpec::Box { width: size, height: size, center: [0.0, 0.0], angle: 0.0 };
+    return draw.create_draw_object_shapes(&vec![shape], color);
+}
+
+fn place(component: &mut VertexComponent, cell: Cell) {
+    component.transform.transform[3][0] = -1.0 + (cell.0 as f32 + 0.5) * CELL;
+    component.transform.transform[3][1] = -1.0 + (cell.1 as f32 + 0.5) * CELL;
+}
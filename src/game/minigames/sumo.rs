@@ -1,30 +1,53 @@
 use std::f32;
-use std::i16;
 
 use game::minigame::MiniGame;
+use game::minigame::MiniGameSettings;
+use game::minigame::Abandonment;
+use game::bot::{BotInput, Difficulty};
 use game::minigame::create_ring;
 use game::minigame::Point;
 
-use physics::{PhysicsSystem, PhysicsComponent};
+use physics::{PhysicsSystem, PhysicsComponent, CollisionEvent, Category, CollisionFilter};
+use physics::{BodySpec, BodySpecs, ShapeSpec};
+use physics::spec::BODIES_FILE;
+use physics::{LAYER_PLAYER, LAYER_WALL, LAYER_ALL};
 use draw;
 use draw::IDENTITY;
 use draw::{Color, DrawSystem, DrawComponent, VertexComponent, TextComponent};
 use input::InputSystem;
 use input::ID;
-use input::InputEvent::{InputAdded, InputRemoved};
+use game::roster::{Roster, RosterPlayer, RosterEvent, PlayerId};
 
 struct Shape {
     vertices: Vec<Point>,
     color: Color,
 }
 
+// Round wins needed to take the match
+const WINS_PER_MATCH: u64 = 5;
+
+// Radii of the ring, players are out once they are completely outside
+const RING_INNER: f32 = 0.9;
+const RING_OUTER: f32 = 0.95;
+
+// Hits weaker than this don't rumble, hits at full strength rumble fully (physics units)
+const MIN_RUMBLE_IMPULSE: f32 = 0.5;
+const FULL_RUMBLE_IMPULSE: f32 = 5.0;
+
+fn player_filter() -> CollisionFilter {
+    CollisionFilter::new(LAYER_PLAYER, LAYER_ALL)
+}
+
 struct Player {
+    player_id: PlayerId,
     alive: bool,
     dead_for: u64,
     //Steps
     deaths: u64,
+    wins: u64,
     name: String,
     color: Color,
+    // None while the player's controller is disconnected, the player is paused until it's back
     controller_inst_id: Option<ID>,
     draw_component: VertexComponent,
     death_count_text: TextComponent,
@@ -36,6 +59,7 @@ struct GameState {
     ring: Ring,
     players: Vec<Player>,
     rounds: u64,
+    player_spec: BodySpec,
 }
 
 struct Ring {
@@ -54,14 +78,17 @@ impl GameState {
     fn new_wall(draw_system: &mut DrawSystem,
                 physics_system: &mut PhysicsSystem,
                 shape: Shape,
-                is_dynamic: bool)
+                spec: &BodySpec)
                 -> Wall {
         let color = shape.color;
         let vertices =
             shape.vertices.iter().map(|v| draw::Point::from_point_and_color(v, color)).collect();
 
         let draw_object = draw_system.create_draw_object(vertices);
-        let physics_object = physics_system.create_body(&shape.vertices, is_dynamic);
+        let spec = spec.clone().with_shape(ShapeSpec::polygon(&shape.vertices));
+        let physics_object = physics_system.create_body(&spec,
+                                                        Category::Wall,
+                                                        CollisionFilter::new(LAYER_WALL, LAYER_ALL));
 
         Wall {
             draw_component: draw_object,
@@ -71,11 +98,11 @@ impl GameState {
 
     fn new_player_object(draw_system: &mut DrawSystem,
                          physics_system: &mut PhysicsSystem,
-                         color: Color,
-                         name: String,
-                         controller_id: Option<ID>
+                         spec: &BodySpec,
+                         roster_player: &RosterPlayer
     ) -> Player {
-        let physics_object = physics_system.create_body_stl(include_bytes!("../../../models/arrow_head.stl"), true);
+        let color = roster_player.color;
+        let physics_object = physics_system.create_body_stl(include_bytes!("../../../models/arrow_head.stl"), spec, Category::Player, player_filter());
 
         let mut text_object = draw_system.create_text();
         text_object.text = format!("{}", 0);
@@ -83,15 +110,17 @@ impl GameState {
         let draw_body_object = draw_system.create_draw_object_stl(include_bytes!("../../../models/arrow_head.stl"), color);
 
         Player {
+            player_id: roster_player.id,
             alive: true,
             dead_for: 0,
             deaths: 0,
+            wins: 0,
             color: color,
-            name: name,
+            name: roster_player.name.clone(),
             draw_component: draw_body_object,
             death_count_text: text_object,
             physics_component: physics_object,
-            controller_inst_id: controller_id,
+            controller_inst_id: roster_player.controller,
         }
     }
 
@@ -102,7 +131,7 @@ impl GameState {
         for player_object in self.players.iter_mut() {
             physics_system.destroy_body(&player_object.physics_component);
 
-            let physics_object = physics_system.create_body_stl(include_bytes!("../../../models/arrow_head.stl"), true);
+            let physics_object = physics_system.create_body_stl(include_bytes!("../../../models/arrow_head.stl"), &self.player_spec, Category::Player, player_filter());
 
             //            let text_object = draw_system.create_text();
             let draw_body_object = draw_system.create_draw_object_stl(include_bytes!("../../../models/arrow_head.stl"), player_object.color);
@@ -112,47 +141,84 @@ impl GameState {
             player_object.physics_component = physics_object;
 
             player_object.alive = true;
+            if player_object.controller_inst_id.is_none() {
+                physics_system.set_active(&player_object.physics_component, false);
+            }
         }
     }
 
     fn new_ring(draw_system: &mut DrawSystem,
                 physics_system: &mut PhysicsSystem) -> Ring {
-        let (draw_object, physics_object) = create_ring(0.9, 0.95, [0.8, 0.02, 0.02], draw_system, physics_system);
+        let (draw_object, physics_object) = create_ring(RING_INNER, RING_OUTER, [0.8, 0.02, 0.02], draw_system, physics_system);
         Ring {
             draw_component: draw_object,
             physics_component: physics_object,
         }
     }
 
-    fn remove_player_object_by_controller_id(&mut self,
-                                             physics_system: &mut PhysicsSystem,
-                                             id: ID) {
-        self.players.retain(|ref p| {
-            match p.controller_inst_id {
-                Some(player_controller_id) => {
-                    if id == player_controller_id {
-                        let p_obj = &p.physics_component;
-                        physics_system.destroy_body(p_obj);
+    fn add_player(&mut self,
+                  draw_system: &mut DrawSystem,
+                  physics_system: &mut PhysicsSystem,
+                  roster_player: &RosterPlayer) {
+        if self.players.iter().any(|p| p.player_id == roster_player.id) {
+            return;
+        }
 
-                        info!("Player removed from game");
+        let player = GameState::new_player_object(draw_system, physics_system, &self.player_spec, roster_player);
+        if player.controller_inst_id.is_none() {
+            physics_system.set_active(&player.physics_component, false);
+        }
+        self.players.push(player);
+        info!("New player added to game");
+    }
 
-                        return false;
-                    }
+    // Paused players keep their place, but can't be pushed or ring out
+    fn set_controller(&mut self,
+                      physics_system: &mut PhysicsSystem,
+                      player_id: PlayerId,
+                      controller: Option<ID>) {
+        for player in self.players.iter_mut().filter(|p| p.player_id == player_id) {
+            player.controller_inst_id = controller;
+            physics_system.set_active(&player.physics_component, controller.is_some());
+        }
+    }
 
-                    return true;
-                }
-                None => true
-            }
-        });
+    fn remove_player(&mut self,
+                     physics_system: &mut PhysicsSystem,
+                     player_id: PlayerId) {
+        for player in self.players.iter().filter(|p| p.player_id == player_id) {
+            physics_system.destroy_body(&player.physics_component);
+            info!("Player removed from game");
+        }
+        self.players.retain(|p| p.player_id != player_id);
+    }
+
+    fn round_over(&self) -> bool {
+        let active: Vec<&Player> = self.players.iter().filter(|p| p.controller_inst_id.is_some()).collect();
+        let single_player = match active.len() {
+            1 => 1,
+            _ => 0,
+        };
+
+        return active.iter().filter(|i| i.alive).count() <= 1 - single_player;
     }
 }
 
 pub struct Sumo {
     state: GameState,
+    abandonment: Abandonment,
 }
 
-impl MiniGame for Sumo {
-    fn new(draw: &mut DrawSystem, physics: &mut PhysicsSystem, input: &InputSystem) -> Sumo {
+impl Sumo {
+    // Sumo has no randomness, the seed is unused
+    pub fn create(draw: &mut DrawSystem, physics: &mut PhysicsSystem, roster: &Roster, _settings: &MiniGameSettings, _seed: u64) -> Box<MiniGame> {
+        return Box::new(Sumo::new(draw, physics, roster));
+    }
+
+    pub fn new(draw: &mut DrawSystem, physics: &mut PhysicsSystem, roster: &Roster) -> Sumo {
+        let specs = BodySpecs::load(BODIES_FILE).expect("Failed to load body specs");
+        let wall_spec = specs.get("sumo_wall");
+
         let ring = GameState::new_ring(draw, physics);
 
         let mut walls: Vec<Wall> = vec![];
@@ -168,7 +234,7 @@ impl MiniGame for Sumo {
                                            ],
                                            color: [0.5, 0.5, 0.5],
                                        },
-                                       false));
+                                       &wall_spec));
         walls.push(GameState::new_wall(draw,
                                        physics,
                                        Shape {
@@ -180,7 +246,7 @@ impl MiniGame for Sumo {
                                            ],
                                            color: [0.5, 0.5, 0.5],
                                        },
-                                       false));
+                                       &wall_spec));
         walls.push(GameState::new_wall(draw,
                                        physics,
                                        Shape {
@@ -192,7 +258,7 @@ impl MiniGame for Sumo {
                                            ],
                                            color: [0.5, 0.5, 0.5],
                                        },
-                                       false));
+                                       &wall_spec));
         walls.push(GameState::new_wall(draw,
                                        physics,
                                        Shape {
@@ -204,33 +270,66 @@ impl MiniGame for Sumo {
                                            ],
                                            color: [0.5, 0.5, 0.5],
                                        },
-                                       false));
+                                       &wall_spec));
+
+        let mut state = GameState {
+            walls: walls,
+            ring: ring,
+            players: vec![],
+            rounds: 0,
+            player_spec: specs.get("sumo_player"),
+        };
+
+        for roster_player in roster.players() {
+            state.add_player(draw, physics, roster_player);
+        }
 
         Sumo {
-            state: GameState {
-                walls: walls,
-                ring: ring,
-                players: vec![],
-                rounds: 0,
-            }
+            state: state,
+            abandonment: Abandonment::new(),
         }
     }
+}
 
+impl MiniGame for Sumo {
     fn done(&self) -> bool {
-        let single_player = match self.state.players.len() {
-            1 => 1,
-            _ => 0,
-        };
+        return self.abandonment.expired() || self.state.players.iter().any(|p| p.wins >= WINS_PER_MATCH);
+    }
 
-        if self.state.players.iter().filter(|i| i.alive).count() <= 1 - single_player {
-            return true;
+    fn step(&mut self, draw: &mut DrawSystem, physics: &mut PhysicsSystem, input: &mut InputSystem, roster: &mut Roster) {
+        for event in roster.events() {
+            match *event {
+                RosterEvent::Joined(id) => {
+                    if let Some(roster_player) = roster.player(id) {
+                        self.state.add_player(draw, physics, roster_player);
+                    }
+                }
+                RosterEvent::Disconnected(id) => self.state.set_controller(physics, id, None),
+                RosterEvent::Reconnected(id) => {
+                    let controller = roster.player(id).and_then(|p| p.controller);
+                    self.state.set_controller(physics, id, controller);
+                }
+                RosterEvent::Left(id) => self.state.remove_player(physics, id),
+            }
         }
 
-        return false;
-    }
+        if self.abandonment.paused(roster, physics.time_step()) {
+            return;
+        }
+
+        if self.state.round_over() {
+            for player in self.state.players.iter_mut() {
+                if player.alive && player.controller_inst_id.is_some() {
+                    player.wins = player.wins + 1;
+                }
+            }
+
+            if let Some(winner) = self.state.players.iter().filter(|p| p.wins >= WINS_PER_MATCH).next() {
+                info!("{} wins the match after {} rounds", winner.name, self.state.rounds + 1);
+                roster.add_score(winner.player_id, 1);
+                return;
+            }
 
-    fn step(&mut self, draw: &mut DrawSystem, physics: &mut PhysicsSystem, input: &mut InputSystem) {
-        if self.done() {
             for player in self.state.players.iter_mut() {
                 player.death_count_text.text = format!("{}", self.state.rounds - player.deaths);
             }
@@ -238,58 +337,43 @@ impl MiniGame for Sumo {
             self.state.revive_player_object(draw, physics);
         }
 
-        // Handle input events
-        'events: loop {
-            match input.event() {
-                Some(InputAdded(id)) => {
-                    let player_colors = [
-                        ([1.0, 0.2, 0.0], "Orange"),
-                        ([0.2, 0.0, 1.0], "Purple"),
-                        ([0.8, 0.0, 0.0], "Red"),
-                        ([0.0, 0.8, 0.0], "Green"),
-                        ([0.8, 0.8, 0.0], "Yellow"),
-                        ([0.0, 0.0, 1.0], "Blue"),
-                        ([0.0, 0.8, 0.8], "Cyan"),
-                        ([0.8, 0.0, 0.8], "Magenta"),
-                    ];
-
-                    let (color, name) = player_colors[id as usize % player_colors.len()];
-                    self.state.players.push(GameState::new_player_object(draw, physics, color, name.into(), Some(id)));
-                    info!("New player added to game");
-                }
-                Some(InputRemoved(id)) => {
-                    info!("Player removal event handling");
-
-                    self.state.remove_player_object_by_controller_id(physics, id)
+        // Ring outs: players no longer overlapping the arena sensor
+        let ring_entity = self.state.ring.physics_component.entity();
+        for collision in physics.collisions() {
+            if let CollisionEvent::Ended { .. } = *collision {
+                if let Some(entity) = collision.other(ring_entity) {
+                    for player in self.state.players.iter_mut() {
+                        let paused = player.controller_inst_id.is_none();
+                        if player.alive && !paused && player.physics_component.entity() == entity {
+                            player.deaths = player.deaths + 1;
+                            player.alive = false;
+                            player.draw_component.set_color([0.05, 0.05, 0.05]);
+                            if let Some(id) = player.controller_inst_id {
+                                input.rumble(id, 1.0, 400);
+                            }
+                        }
+                    }
                 }
-                None => { break 'events }
             }
         }
 
-        {
-            let ring = &self.state.ring;
-            let rounds = self.state.rounds;
-            let players = &mut self.state.players;
-            let ring_phys = &ring.physics_component;
-
-            //TODO change this to a for_each
-            let _ = physics.for_collisions(ring_phys, &mut |contact| {
-                //                let mut players = &self.state.players;
-                let (body_handle, fixture_handle) = contact.fixture_b();
-
-                for player in players.iter_mut() {
-                    let alive = player.alive;
-                    let handle = &player.physics_component.body_handle;
-                    if alive && handle == &body_handle {
-                        if player.alive {
-                            player.deaths = player.deaths + 1;
-                        }
-                        player.alive = false;
-                        let draw_obj = &mut player.draw_component;
-                        draw_obj.set_color([0.05, 0.05, 0.05]);
+        // Hard hits against other players and walls
+        for collision in physics.collisions() {
+            if let CollisionEvent::Impact { a, b, impulse } = *collision {
+                if impulse < MIN_RUMBLE_IMPULSE {
+                    continue;
+                }
+                let strength = (impulse / FULL_RUMBLE_IMPULSE).min(1.0);
+                for player in self.state.players.iter().filter(|p| p.alive) {
+                    let entity = player.physics_component.entity();
+                    if entity != a && entity != b {
+                        continue;
+                    }
+                    if let Some(id) = player.controller_inst_id {
+                        input.rumble(id, strength, 150);
                     }
                 }
-            });
+            }
         }
 
         for player in &mut self.state.players.iter_mut() {
@@ -297,8 +381,8 @@ impl MiniGame for Sumo {
                 player.death_count_text.color = player.color;
                 if let Some(id) = player.controller_inst_id {
                     if let Some(ctrlr_state) = input.get_controller_state(id) {
-                        let x = ctrlr_state.axis_l_x as f32 / i16::MAX as f32; // * 55.0;
-                        let y = (ctrlr_state.axis_l_y as f32 / i16::MAX as f32) * -1.0; // * -55.0;
+                        let x = ctrlr_state.left_stick[0];
+                        let y = -ctrlr_state.left_stick[1];
                         physics.apply_force_to_center([x, y, 0.0], &player.physics_component);
                     } else {
                         info!("Input system couldn't find assigned controller {:?}", id);
@@ -347,13 +431,17 @@ impl MiniGame for Sumo {
 
         // Graphics step (just set the component inputs)
         for (i, player) in self.state.players.iter_mut().enumerate() {
-            player.draw_component.transform.transform = physics.get_transformation(&player.physics_component);
             player.death_count_text.transform = text_pos(i);
         }
     }
 
 
-    fn render(&mut self, draw_system: &mut DrawSystem) -> () {
+    fn render(&mut self, draw_system: &mut DrawSystem, physics: &PhysicsSystem, alpha: f32) -> () {
+        for player in self.state.players.iter_mut() {
+            player.draw_component.transform.transform =
+                physics.get_interpolated_transformation(&player.physics_component, alpha);
+        }
+
         draw_system.draw(&mut self.state.ring.draw_component);
 
         for wall in self.state.walls.iter_mut() {
@@ -365,4 +453,62 @@ impl MiniGame for Sumo {
             draw_system.draw(&mut player.death_count_text);
         }
     }
+
+    // Charge the nearest opponent, but turn back towards the center when about to leave the ring
+    fn bot_input(&self, controller: ID, difficulty: Difficulty, physics: &PhysicsSystem) -> BotInput {
+        let me = match self.state.players.iter().filter(|p| p.alive && p.controller_inst_id == Some(controller)).next() {
+            Some(player) => player,
+            None => return BotInput::idle(),
+        };
+
+        let position = physics.position(&me.physics_component);
+        let velocity = physics.velocity(&me.physics_component);
+
+        let nearest = self.state.players.iter()
+            .filter(|p| p.alive && p.controller_inst_id != Some(controller))
+            .map(|p| physics.position(&p.physics_component))
+            .map(|p| ([p[0] - position[0], p[1] - position[1]], (p[0] - position[0]).hypot(p[1] - position[1])))
+            .fold(None, |nearest: Option<([f32; 2], f32)>, (offset, distance)| match nearest {
+                Some((_, best)) if best <= distance => nearest,
+                _ => Some((offset, distance)),
+            });
+
+        let mut direction = match nearest {
+            Some((offset, distance)) if distance > 0.0 => [offset[0] / distance, offset[1] / distance],
+            _ => [0.0, 0.0],
+        };
+
+        // Easier bots notice the edge later
+        let caution = match difficulty {
+            Difficulty::Easy => 0.85,
+            Difficulty::Normal => 0.7,
+            Difficulty::Hard => 0.55,
+        };
+
+        // Where the bot will be in half a second
+        let predicted = [position[0] + velocity[0] * 0.5, position[1] + velocity[1] * 0.5];
+        let radius = predicted[0].hypot(predicted[1]);
+        let safe_radius = RING_OUTER * caution;
+
+        if radius > safe_radius {
+            let urgency = ((radius - safe_radius) / (RING_OUTER - safe_radius)).min(1.0);
+            let inwards = [-predicted[0] / radius, -predicted[1] / radius];
+            direction = [direction[0] * (1.0 - urgency) + inwards[0] * urgency,
+                         direction[1] * (1.0 - urgency) + inwards[1] * urgency];
+        }
+
+        return BotInput::towards(direction);
+    }
+
+    fn teardown(&mut self, physics: &mut PhysicsSystem) {
+        physics.destroy_body(&self.state.ring.physics_component);
+
+        for wall in self.state.walls.iter() {
+            physics.destroy_body(&wall.physics_component);
+        }
+
+        for player in self.state.players.drain(..) {
+            physics.destroy_body(&player.physics_component);
+        }
+    }
 }
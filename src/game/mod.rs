@@ -1,2 +1,7 @@
 pub mod minigame;
-pub mod minigames;
\ No newline at end of file
+pub mod minigames;
+pub mod registry;
+pub mod session;
+pub mod bot;
+pub mod roster;
+pub mod lobby;
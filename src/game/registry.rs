@@ -0,0 +1,66 @@
+use draw::DrawSystem;
+use physics::PhysicsSystem;
+use game::roster::Roster;
+
+use game::minigame::{MiniGame, MiniGameConstructor, MiniGameSettings};
+use game::minigames::avoid_the_wall::AvoidTheWall;
+use game::minigames::couch_maze::CouchMaze;
+use game::minigames::marble_maze::MarbleMaze;
+use game::minigames::racing::Racing;
+use game::minigames::snake::Snake;
+use game::minigames::sumo::Sumo;
+
+pub struct MiniGameRegistry {
+    entries: Vec<(&'static str, MiniGameConstructor)>,
+    // Handed to every minigame created
+    settings: MiniGameSettings,
+}
+
+impl MiniGameRegistry {
+    pub fn new(settings: MiniGameSettings) -> MiniGameRegistry {
+        MiniGameRegistry {
+            entries: vec![],
+            settings: settings,
+        }
+    }
+
+    // Registry containing every minigame that ships with the game
+    pub fn with_defaults(settings: MiniGameSettings) -> MiniGameRegistry {
+        let mut registry = MiniGameRegistry::new(settings);
+        registry.register("sumo", Sumo::create);
+        registry.register("snake", Snake::create);
+        registry.register("racing", Racing::create);
+        registry.register("marble_maze", MarbleMaze::create);
+        registry.register("couch_maze", CouchMaze::create);
+        registry.register("avoid_the_wall", AvoidTheWall::create);
+        return registry;
+    }
+
+    pub fn register(&mut self, name: &'static str, constructor: MiniGameConstructor) {
+        if self.contains(name) {
+            warn!("Minigame {:?} registered twice, replacing", name);
+            self.entries.retain(|&(n, _)| n != name);
+        }
+        self.entries.push((name, constructor));
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        return self.entries.iter().any(|&(n, _)| n == name);
+    }
+
+    pub fn names(&self) -> Vec<&'static str> {
+        return self.entries.iter().map(|&(n, _)| n).collect();
+    }
+
+    pub fn create(&self,
+                  name: &str,
+                  draw: &mut DrawSystem,
+                  physics: &mut PhysicsSystem,
+                  roster: &Roster,
+                  seed: u64) -> Option<Box<MiniGame>> {
+        return self.entries.iter()
+            .filter(|&&(n, _)| n == name)
+            .next()
+            .map(|&(_, constructor)| constructor(draw, physics, roster, &self.settings, seed));
+    }
+}
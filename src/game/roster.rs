@@ -0,0 +1,289 @@
+use input::{InputSystem, ID};
+use input::InputEvent::{InputAdded, InputRemoved};
+use game::minigame::Color;
+
+pub const PLAYER_COLORS: [(Color, &str); 8] = [
+    ([1.0, 0.2, 0.0], "Orange"),
+    ([0.2, 0.0, 1.0], "Purple"),
+    ([0.8, 0.0, 0.0], "Red"),
+    ([0.0, 0.8, 0.0], "Green"),
+    ([0.8, 0.8, 0.0], "Yellow"),
+    ([0.0, 0.0, 1.0], "Blue"),
+    ([0.0, 0.8, 0.8], "Cyan"),
+    ([0.8, 0.0, 0.8], "Magenta"),
+];
+
+// Stays the same for the whole session, unlike controller ids
+pub type PlayerId = u32;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RosterEvent {
+    Joined(PlayerId),
+    // The controller went away, the player keeps their place and score
+    Disconnected(PlayerId),
+    Reconnected(PlayerId),
+    Left(PlayerId),
+}
+
+#[derive(Debug, Clone)]
+pub struct RosterPlayer {
+    pub id: PlayerId,
+    // GUID of the controller the player joined with
+    pub guid: String,
+    pub name: String,
+    pub color: Color,
+    // None while disconnected
+    pub controller: Option<ID>,
+    // Points over the whole session
+    pub score: u64,
+}
+
+impl RosterPlayer {
+    pub fn connected(&self) -> bool {
+        return self.controller.is_some();
+    }
+}
+
+// The people playing, separate from the controllers they hold. A controller that
+// reconnects gets back the player of the last controller with the same GUID that left.
+// GUIDs identify the controller model, so two identical controllers can swap players
+// if both are unplugged at once. Other controllers stay unassigned until they join.
+pub struct Roster {
+    players: Vec<RosterPlayer>,
+    // Connected controllers without a player, with their GUIDs
+    unassigned: Vec<(ID, String)>,
+    next_id: PlayerId,
+    // What happened during the last update
+    events: Vec<RosterEvent>,
+}
+
+impl Roster {
+    pub fn new() -> Roster {
+        Roster {
+            players: vec![],
+            unassigned: vec![],
+            next_id: 0,
+            events: vec![],
+        }
+    }
+
+    pub fn players(&self) -> &[RosterPlayer] {
+        return &self.players;
+    }
+
+    pub fn player(&self, id: PlayerId) -> Option<&RosterPlayer> {
+        return self.players.iter().filter(|p| p.id == id).next();
+    }
+
+    pub fn player_mut(&mut self, id: PlayerId) -> Option<&mut RosterPlayer> {
+        return self.players.iter_mut().filter(|p| p.id == id).next();
+    }
+
+    pub fn by_controller(&self, controller: ID) -> Option<&RosterPlayer> {
+        return self.players.iter().filter(|p| p.controller == Some(controller)).next();
+    }
+
+    pub fn unassigned(&self) -> Vec<ID> {
+        return self.unassigned.iter().map(|&(controller, _)| controller).collect();
+    }
+
+    pub fn events(&self) -> &[RosterEvent] {
+        return &self.events;
+    }
+
+    pub fn add_score(&mut self, id: PlayerId, points: u64) {
+        if let Some(player) = self.player_mut(id) {
+            player.score = player.score + points;
+        }
+    }
+
+    // Handle the controllers added and removed since the last update, once per tick
+    pub fn update(&mut self, input: &mut InputSystem) {
+        self.events.clear();
+
+        while let Some(event) = input.event() {
+            match event {
+                InputAdded(controller) => {
+                    let guid = input.get_controller_state(controller).map(|s| s.guid.clone()).unwrap_or(String::new());
+                    self.connect(controller, guid);
+                }
+                InputRemoved(controller) => self.disconnect(controller),
+            }
+        }
+    }
+
+    // New player for an unassigned controller
+    pub fn join(&mut self, controller: ID) -> Option<PlayerId> {
+        let index = self.unassigned.iter().position(|u| u.0 == controller);
+        let guid = match index {
+            Some(index) => self.unassigned.remove(index).1,
+            None => return None,
+        };
+
+        let id = self.next_id;
+        self.next_id = self.next_id + 1;
+
+        let (color, name) = self.free_color();
+        info!("{} joined with controller {:?}", name, controller);
+
+        self.players.push(RosterPlayer {
+            id: id,
+            guid: guid,
+            name: name.into(),
+            color: color,
+            controller: Some(controller),
+            score: 0,
+        });
+        self.events.push(RosterEvent::Joined(id));
+        return Some(id);
+    }
+
+    // Remove a player, their controller becomes unassigned again
+    pub fn leave(&mut self, id: PlayerId) {
+        let controller = self.player(id).and_then(|p| p.controller.map(|c| (c, p.guid.clone())));
+        if let Some(unassigned) = controller {
+            self.unassigned.push(unassigned);
+        }
+
+        let before = self.players.len();
+        self.players.retain(|p| p.id != id);
+        if self.players.len() != before {
+            info!("Player {} left", id);
+            self.events.push(RosterEvent::Left(id));
+        }
+    }
+
+    pub fn set_name(&mut self, id: PlayerId, name: String) {
+        if let Some(player) = self.player_mut(id) {
+            player.name = name;
+        }
+    }
+
+    // Switch to the next color in the palette nobody else uses, backwards if `forward` is false
+    pub fn cycle_color(&mut self, id: PlayerId, forward: bool) {
+        let current = match self.player(id) {
+            Some(player) => PLAYER_COLORS.iter().position(|&(color, _)| color == player.color).unwrap_or(0),
+            None => return,
+        };
+
+        let count = PLAYER_COLORS.len();
+        for offset in 1..count {
+            let index = match forward {
+                true => (current + offset) % count,
+                false => (current + count - offset) % count,
+            };
+            let color = PLAYER_COLORS[index].0;
+            if !self.players.iter().any(|p| p.id != id && p.color == color) {
+                if let Some(player) = self.player_mut(id) {
+                    player.color = color;
+                }
+                return;
+            }
+        }
+    }
+
+    fn connect(&mut self, controller: ID, guid: String) {
+        if self.by_controller(controller).is_some() || self.unassigned.iter().any(|u| u.0 == controller) {
+            return;
+        }
+
+        if let Some(player) = self.players.iter_mut().filter(|p| !p.connected() && p.guid == guid).next() {
+            info!("{} reconnected with controller {:?}", player.name, controller);
+            player.controller = Some(controller);
+            self.events.push(RosterEvent::Reconnected(player.id));
+            return;
+        }
+
+        self.unassigned.push((controller, guid));
+    }
+
+    fn disconnect(&mut self, controller: ID) {
+        self.unassigned.retain(|u| u.0 != controller);
+
+        if let Some(player) = self.players.iter_mut().filter(|p| p.controller == Some(controller)).next() {
+            info!("{} disconnected", player.name);
+            player.controller = None;
+            self.events.push(RosterEvent::Disconnected(player.id));
+        }
+    }
+
+    // First color nobody uses, colors repeat once there are more players than colors
+    fn free_color(&self) -> (Color, &'static str) {
+        for &(color, name) in PLAYER_COLORS.iter() {
+            if !self.players.iter().any(|p| p.color == color) {
+                return (color, name);
+            }
+        }
+        return PLAYER_COLORS[self.players.len() % PLAYER_COLORS.len()];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAD: &'static str = "030000005e0400008e02000014010000";
+    const STICK: &'static str = "030000006d04000015c2000010010000";
+
+    #[test]
+    fn reconnect_by_guid_keeps_player() {
+        let mut roster = Roster::new();
+        roster.connect(0, PAD.to_string());
+        let id = roster.join(0).unwrap();
+        roster.add_score(id, 3);
+
+        roster.events.clear();
+        roster.disconnect(0);
+        assert_eq!(roster.events(), &[RosterEvent::Disconnected(id)]);
+        assert!(!roster.player(id).unwrap().connected());
+
+        // SDL hands out a new instance id when a controller comes back
+        roster.events.clear();
+        roster.connect(5, PAD.to_string());
+        assert_eq!(roster.events(), &[RosterEvent::Reconnected(id)]);
+
+        let player = roster.player(id).unwrap();
+        assert_eq!(player.controller, Some(5));
+        assert_eq!(player.score, 3);
+        assert!(roster.unassigned().is_empty());
+    }
+
+    #[test]
+    fn other_guid_stays_unassigned() {
+        let mut roster = Roster::new();
+        roster.connect(0, PAD.to_string());
+        let id = roster.join(0).unwrap();
+        roster.disconnect(0);
+
+        roster.events.clear();
+        roster.connect(1, STICK.to_string());
+        assert!(roster.events().is_empty());
+        assert_eq!(roster.unassigned(), vec![1]);
+        assert_eq!(roster.player(id).unwrap().controller, None);
+    }
+
+    #[test]
+    fn connected_player_is_not_taken_over() {
+        let mut roster = Roster::new();
+        roster.connect(0, PAD.to_string());
+        let id = roster.join(0).unwrap();
+
+        // A second controller of the same model
+        roster.connect(1, PAD.to_string());
+        assert_eq!(roster.player(id).unwrap().controller, Some(0));
+        assert_eq!(roster.unassigned(), vec![1]);
+    }
+
+    #[test]
+    fn left_player_does_not_reconnect() {
+        let mut roster = Roster::new();
+        roster.connect(0, PAD.to_string());
+        let id = roster.join(0).unwrap();
+        roster.disconnect(0);
+        roster.leave(id);
+
+        roster.connect(2, PAD.to_string());
+        assert!(roster.player(id).is_none());
+        assert_eq!(roster.unassigned(), vec![2]);
+    }
+}
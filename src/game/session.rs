@@ -0,0 +1,150 @@
+use sdl2::controller::Button;
+
+use draw::DrawSystem;
+use physics::PhysicsSystem;
+use input::{InputSystem, ID, is_virtual_id};
+
+use game::minigame::MiniGame;
+use game::registry::MiniGameRegistry;
+use game::bot::{BotInput, Difficulty};
+use game::roster::Roster;
+use game::lobby::Lobby;
+use rng::Rng;
+
+// Plays the minigames of a playlist one after another, looping back to the start
+pub struct Session {
+    registry: MiniGameRegistry,
+    playlist: Vec<String>,
+    next: usize,
+    current: Option<(String, Box<MiniGame>)>,
+    finished: u32,
+    // Hands each minigame its seed
+    rng: Rng,
+    // Players stay in the roster from game to game
+    roster: Roster,
+    // Until everyone in the lobby is ready
+    lobby: Option<Lobby>,
+}
+
+impl Session {
+    pub fn new(registry: MiniGameRegistry, playlist: Vec<String>, seed: u64, draw: &DrawSystem) -> Session {
+        let mut valid_playlist: Vec<String> = vec![];
+
+        for name in playlist {
+            if registry.contains(&name) {
+                valid_playlist.push(name);
+            } else {
+                warn!("Unknown minigame {:?} in playlist (available: {:?})", name, registry.names());
+            }
+        }
+
+        if valid_playlist.is_empty() {
+            panic!("No playable minigames in playlist (available: {:?})", registry.names());
+        }
+
+        Session {
+            registry: registry,
+            playlist: valid_playlist,
+            next: 0,
+            current: None,
+            finished: 0,
+            rng: Rng::new(seed),
+            roster: Roster::new(),
+            lobby: Some(Lobby::new(draw)),
+        }
+    }
+
+    pub fn current_name(&self) -> Option<&str> {
+        return self.current.as_ref().map(|&(ref name, _)| name.as_ref());
+    }
+
+    // Number of minigames played to completion
+    pub fn finished_count(&self) -> u32 {
+        return self.finished;
+    }
+
+    pub fn roster(&self) -> &Roster {
+        return &self.roster;
+    }
+
+    pub fn step(&mut self, draw: &mut DrawSystem, physics: &mut PhysicsSystem, input: &mut InputSystem) {
+        self.roster.update(input);
+
+        if let Some(ref mut lobby) = self.lobby {
+            lobby.step(draw, input, &mut self.roster);
+        }
+        match self.lobby.as_ref().map(|lobby| lobby.done(&self.roster)) {
+            Some(true) => info!("Everyone is ready"),
+            Some(false) => return,
+            None => self.join_late(input),
+        }
+        self.lobby = None;
+
+        if self.current.is_none() {
+            self.start_next(draw, physics);
+        }
+
+        let finished = match self.current {
+            Some((_, ref mut minigame)) => {
+                minigame.step(draw, physics, input, &mut self.roster);
+                minigame.done()
+            }
+            None => false,
+        };
+
+        if finished {
+            self.finished = self.finished + 1;
+            self.finish_current(physics);
+            self.start_next(draw, physics);
+        }
+    }
+
+    pub fn render(&mut self, draw: &mut DrawSystem, physics: &PhysicsSystem, alpha: f32) {
+        if let Some(ref mut lobby) = self.lobby {
+            lobby.render(draw);
+        }
+        if let Some((_, ref mut minigame)) = self.current {
+            minigame.render(draw, physics, alpha);
+        }
+    }
+
+    pub fn bot_input(&self, controller: ID, difficulty: Difficulty, physics: &PhysicsSystem) -> BotInput {
+        return match self.current {
+            Some((_, ref minigame)) => minigame.bot_input(controller, difficulty, physics),
+            None => BotInput::idle(),
+        };
+    }
+
+    // Tear down the running minigame, if any. Dropping it releases its draw components.
+    pub fn finish_current(&mut self, physics: &mut PhysicsSystem) {
+        if let Some((name, mut minigame)) = self.current.take() {
+            info!("Minigame {:?} finished", name);
+            minigame.teardown(physics);
+            // Don't let a game's custom filtering leak into the next one
+            physics.clear_contact_filter();
+        }
+    }
+
+    // Controllers that connect once the lobby is gone join the running game with Start or A,
+    // minigames add them on RosterEvent::Joined like anyone else
+    fn join_late(&mut self, input: &InputSystem) {
+        for controller in self.roster.unassigned() {
+            if is_virtual_id(controller)
+                || input.just_pressed(controller, Button::Start)
+                || input.just_pressed(controller, Button::A) {
+                self.roster.join(controller);
+            }
+        }
+    }
+
+    fn start_next(&mut self, draw: &mut DrawSystem, physics: &mut PhysicsSystem) {
+        let name = self.playlist[self.next].clone();
+        self.next = (self.next + 1) % self.playlist.len();
+
+        info!("Starting minigame {:?}", name);
+        let seed = self.rng.next_u64();
+        let minigame = self.registry.create(&name, draw, physics, &self.roster, seed)
+            .expect("Playlist minigame missing from registry");
+        self.current = Some((name, minigame));
+    }
+}
@@ -0,0 +1,164 @@
+use std::f32;
+
+use config::Config;
+use draw::{DrawSystem, DrawCall};
+use physics::PhysicsSystem;
+use input::{InputSystem, RecordingRumble, ID};
+use game::minigame::MiniGameSettings;
+use game::registry::MiniGameRegistry;
+use game::session::Session;
+use replay::ReplayRecorder;
+use game::bot::Bots;
+
+const DEFAULT_FRAMES: u64 = 600;
+const DEFAULT_PLAYERS: u32 = 2;
+
+#[derive(Debug)]
+pub struct HeadlessOptions {
+    pub frames: u64,
+    pub players: u32,
+    // Play only this minigame instead of the configured playlist
+    pub minigame: Option<String>,
+    pub seed: u64,
+    // Bot players on top of the scripted ones, overrides [bots] count
+    pub bots: Option<u32>,
+    // Save a replay of the run to this file
+    pub record: Option<String>,
+}
+
+impl HeadlessOptions {
+    // Returns None unless --headless was passed
+    pub fn from_args<I: Iterator<Item = String>>(args: I) -> Option<HeadlessOptions> {
+        let mut headless = false;
+        let mut options = HeadlessOptions {
+            frames: DEFAULT_FRAMES,
+            players: DEFAULT_PLAYERS,
+            minigame: None,
+            seed: 0,
+            bots: None,
+            record: None,
+        };
+
+        let mut args = args.skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_ref() {
+                "--headless" => headless = true,
+                "--frames" => options.frames = parse_value(&arg, args.next()),
+                "--players" => options.players = parse_value(&arg, args.next()),
+                "--minigame" => options.minigame = Some(args.next().expect("--minigame needs a name")),
+                "--seed" => options.seed = parse_value(&arg, args.next()),
+                "--bots" => options.bots = Some(parse_value(&arg, args.next())),
+                "--record" => options.record = Some(args.next().expect("--record needs a file")),
+                _ => warn!("Ignoring unknown argument {:?}", arg),
+            }
+        }
+
+        return match headless {
+            true => Some(options),
+            false => None,
+        };
+    }
+}
+
+fn parse_value<T: ::std::str::FromStr>(flag: &str, value: Option<String>) -> T {
+    return match value.as_ref().and_then(|v| v.parse().ok()) {
+        Some(parsed) => parsed,
+        None => panic!("{} needs a numeric value, got {:?}", flag, value),
+    };
+}
+
+// Step minigames without a window or GL context and print the final state
+pub fn run(config: Config, options: HeadlessOptions) {
+    info!("Running headless: {:?}", options);
+
+    let mut draw_system = DrawSystem::new_headless();
+    // One tick per frame so runs are reproducible regardless of machine speed
+    let dt = 1.0 / config.simulation.tick_rate as f32;
+    let mut physics_system = PhysicsSystem::new(dt);
+    let mut input_system = InputSystem::new_headless(config.input);
+    let rumble = RecordingRumble::new();
+    let rumble_log = rumble.log();
+    input_system.set_rumble_backend(Box::new(rumble));
+
+    let players: Vec<ID> = (0..options.players).map(|_| input_system.add_virtual_controller()).collect();
+
+    let mut bot_config = config.bots;
+    if let Some(count) = options.bots {
+        bot_config.count = count;
+    }
+    let mut bots = Bots::from_config(&bot_config, &mut input_system, options.seed);
+
+    let playlist = match options.minigame {
+        Some(ref name) => vec![name.clone()],
+        None => config.session.minigames,
+    };
+    let settings = MiniGameSettings { avoid_the_wall: config.avoid_the_wall };
+    let mut recorder = options.record.clone()
+        .map(|path| ReplayRecorder::new(path, options.seed, dt, &playlist, &settings, &mut input_system));
+
+    let mut session = Session::new(MiniGameRegistry::with_defaults(settings), playlist, options.seed, &draw_system);
+
+    for frame in 0..options.frames {
+        script_input(&mut input_system, &players, frame);
+        bots.update(&session, &physics_system, &mut input_system);
+        input_system.tick(dt);
+
+        if let Some(ref mut recorder) = recorder {
+            recorder.record_tick(&mut input_system);
+        }
+
+        draw_system.pre_render();
+
+        session.step(&mut draw_system, &mut physics_system, &mut input_system);
+        session.render(&mut draw_system, &physics_system, 1.0);
+
+        draw_system.post_render();
+    }
+
+    report(&session, &draw_system, &physics_system);
+    println!("Rumbles: {}", rumble_log.borrow().len());
+
+    if let Some(recorder) = recorder {
+        recorder.finish(&physics_system);
+    }
+
+    session.finish_current(&mut physics_system);
+}
+
+// Each player circles the stick at a different phase and taps A every second
+fn script_input(input: &mut InputSystem, players: &Vec<ID>, frame: u64) {
+    let count = players.len() as f32;
+
+    for (i, id) in players.iter().enumerate() {
+        if let Some(state) = input.get_controller_state_mut(*id) {
+            let angle = frame as f32 * 0.02 + (i as f32 / count) * 2.0 * f32::consts::PI;
+            state.set_left_stick([angle.cos(), angle.sin()]);
+            state.button_a = frame % 60 == 0;
+        }
+    }
+}
+
+fn report(session: &Session, draw: &DrawSystem, physics: &PhysicsSystem) {
+    println!("Headless run finished after {} frames", draw.frame_count());
+    println!("Minigames finished: {}", session.finished_count());
+    println!("Current minigame: {}", session.current_name().unwrap_or("none"));
+
+    let calls = draw.draw_calls();
+    let text_calls = calls.iter().filter(|c| match **c {
+        DrawCall::Text { .. } => true,
+        _ => false,
+    }).count();
+    println!("Draw calls in last frame: {} ({} text)", calls.len(), text_calls);
+
+    for call in calls {
+        if let DrawCall::Text { ref text, .. } = *call {
+            println!("  text {:?}", text);
+        }
+    }
+
+    let bodies = physics.body_positions();
+    println!("Bodies: {}", bodies.len());
+    for (i, &(position, angle)) in bodies.iter().enumerate() {
+        println!("  body {}: position ({:.4}, {:.4}) angle {:.4}", i, position[0], position[1], angle);
+    }
+}
@@ -0,0 +1,92 @@
+use sdl2::controller::Button;
+
+use super::{ControllerState, ID};
+
+// Bits of the button masks. The triggers count as buttons once pulled past their threshold.
+pub const TRIGGER_LEFT_BIT: u32 = 1 << 15;
+pub const TRIGGER_RIGHT_BIT: u32 = 1 << 16;
+
+pub fn button_bit(button: Button) -> u32 {
+    let index = match button {
+        Button::X => 0,
+        Button::Y => 1,
+        Button::A => 2,
+        Button::B => 3,
+        Button::LeftShoulder => 4,
+        Button::RightShoulder => 5,
+        Button::Guide => 6,
+        Button::Back => 7,
+        Button::Start => 8,
+        Button::LeftStick => 9,
+        Button::RightStick => 10,
+        Button::DPadUp => 11,
+        Button::DPadDown => 12,
+        Button::DPadLeft => 13,
+        Button::DPadRight => 14,
+    };
+    return 1 << index;
+}
+
+// Input of every controller as it was at the start of a tick. Stays the same for the whole
+// tick, so every game and menu looking at it during the step sees the same presses.
+#[derive(Debug, Clone, Default)]
+pub struct InputFrame {
+    // Ticks since the input system was created
+    pub tick: u64,
+    pub controllers: Vec<ControllerFrame>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ControllerFrame {
+    pub id: ID,
+    // Buttons held at the end of the tick
+    pub buttons: u32,
+    // Buttons that went down or up during the tick. A tap shorter than a tick shows up in both.
+    pub pressed: u32,
+    pub released: u32,
+    pub left_stick: [f32; 2],
+    pub right_stick: [f32; 2],
+    pub left_trigger: f32,
+    pub right_trigger: f32,
+}
+
+impl InputFrame {
+    pub fn controller(&self, id: ID) -> Option<&ControllerFrame> {
+        return self.controllers.iter().filter(|c| c.id == id).next();
+    }
+
+    pub fn just_pressed(&self, id: ID, button: Button) -> bool {
+        return self.controller(id).map_or(false, |c| c.just_pressed(button));
+    }
+
+    pub fn just_released(&self, id: ID, button: Button) -> bool {
+        return self.controller(id).map_or(false, |c| c.just_released(button));
+    }
+}
+
+impl ControllerFrame {
+    pub fn from_state(state: &ControllerState) -> ControllerFrame {
+        ControllerFrame {
+            id: state.inst_id,
+            buttons: state.buttons(),
+            pressed: state.pressed,
+            released: state.released,
+            left_stick: state.left_stick,
+            right_stick: state.right_stick,
+            left_trigger: state.left_trigger,
+            right_trigger: state.right_trigger,
+        }
+    }
+
+    pub fn is_down(&self, button: Button) -> bool {
+        return self.buttons & button_bit(button) != 0;
+    }
+
+    pub fn just_pressed(&self, button: Button) -> bool {
+        return self.pressed & button_bit(button) != 0;
+    }
+
+    pub fn just_released(&self, button: Button) -> bool {
+        return self.released & button_bit(button) != 0;
+    }
+}
@@ -0,0 +1,765 @@
+mod response;
+mod frame;
+mod rumble;
+
+use std::collections::VecDeque;
+use std;
+
+use config::{InputConfig, KeyMapConfig};
+
+use self::response::Response;
+pub use self::response::ResponseCurve;
+pub use self::frame::{InputFrame, ControllerFrame};
+use self::frame::{button_bit, TRIGGER_LEFT_BIT, TRIGGER_RIGHT_BIT};
+pub use self::rumble::{RumbleBackend, SdlRumble, RecordingRumble, RumbleRequest};
+
+use sdl2::controller::GameController;
+use sdl2::controller::Button;
+use sdl2::controller::Axis;
+use sdl2::keyboard::Keycode;
+use sdl2::event::Event;
+use sdl2::GameControllerSubsystem;
+use sdl2::JoystickSubsystem;
+use sdl2::Sdl;
+
+use self::InputEvent::{InputAdded, InputRemoved};
+
+// What a keyboard player gets when holding both keys of an axis
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OpposingKeys {
+    // The key pressed last wins
+    Last,
+    // The axis stays centered
+    Cancel,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InputEvent {
+    InputAdded(ID),
+    InputRemoved(ID),
+}
+
+pub struct InputSystem {
+    config: InputConfig,
+    // None when running headless
+    sdl_controller_subsystem: Option<GameControllerSubsystem>,
+    // Only used to look up controller GUIDs
+    sdl_joystick_subsystem: Option<JoystickSubsystem>,
+    open_sdl_controllers: Vec<GameController>,
+    controller_states: Vec<ControllerState>,
+    // One per keyboard player
+    keymaps: Vec<KeyMap>,
+    // Frozen by tick(), what games read during a step
+    frame: InputFrame,
+    rumble: Box<RumbleBackend>,
+    pub event_queue: VecDeque<InputEvent>,
+    next_virtual_id: ID,
+    // Copy of every queued event while a replay is being recorded
+    event_log: Option<Vec<InputEvent>>,
+}
+
+pub type ID = i32;
+
+const KEYBOARD_ID: ID = -1;
+const KEYBOARD_COUNT: ID = 8;
+// Virtual controllers (scripted input, bots) count down from here
+const VIRTUAL_ID: ID = -100;
+
+fn is_keyboard_id(id: ID) -> bool {
+    return id <= KEYBOARD_ID && id > KEYBOARD_ID - KEYBOARD_COUNT;
+}
+
+// Controllers driven by code, bots and scripted input
+pub fn is_virtual_id(id: ID) -> bool {
+    return id <= VIRTUAL_ID;
+}
+
+// Keys of one keyboard player, resolved from the config
+struct KeyMap {
+    id: ID,
+    axes: Vec<(Keycode, Axis, i16)>,
+    buttons: Vec<(Keycode, Button)>,
+    // Direction keys currently down, in the order they were pressed
+    held: Vec<Keycode>,
+    // Stick position written to the controller, x right and y down like SDL
+    stick: [f32; 2],
+}
+
+impl KeyMap {
+    fn from_config(id: ID, config: &KeyMapConfig) -> KeyMap {
+        let mut axes = vec![];
+        for &(ref name, axis, direction) in [(&config.up, Axis::LeftY, -1),
+                                             (&config.down, Axis::LeftY, 1),
+                                             (&config.left, Axis::LeftX, -1),
+                                             (&config.right, Axis::LeftX, 1)].iter() {
+            if let Some(key) = parse_key(name) {
+                axes.push((key, axis, direction));
+            }
+        }
+
+        let mut buttons = vec![];
+        for (button_name, key_name) in config.buttons.iter() {
+            match Button::from_string(button_name) {
+                Some(button) => {
+                    if let Some(key) = parse_key(key_name) {
+                        buttons.push((key, button));
+                    }
+                }
+                None => warn!("Unknown controller button {:?} in key map", button_name),
+            }
+        }
+
+        KeyMap {
+            id: id,
+            axes: axes,
+            buttons: buttons,
+            held: vec![],
+            stick: [0.0, 0.0],
+        }
+    }
+
+    // Stick position the held keys ask for, diagonals have the length of a full stick
+    fn target(&self, opposing: OpposingKeys) -> [f32; 2] {
+        let mut target = [0.0, 0.0];
+        for (i, axis) in [Axis::LeftX, Axis::LeftY].iter().enumerate() {
+            // Position in `held` of the last negative and positive key down on this axis
+            let mut negative = None;
+            let mut positive = None;
+            for (order, key) in self.held.iter().enumerate() {
+                for &(_, _, direction) in self.axes.iter().filter(|&&(k, a, _)| k == *key && a == *axis) {
+                    if direction < 0 {
+                        negative = Some(order);
+                    } else {
+                        positive = Some(order);
+                    }
+                }
+            }
+
+            target[i] = match (negative, positive, opposing) {
+                (Some(_), None, _) => -1.0,
+                (None, Some(_), _) => 1.0,
+                (Some(n), Some(p), OpposingKeys::Last) => if n > p { -1.0 } else { 1.0 },
+                _ => 0.0,
+            };
+        }
+
+        let length = (target[0] * target[0] + target[1] * target[1]).sqrt();
+        if length > 1.0 {
+            target = [target[0] / length, target[1] / length];
+        }
+        return target;
+    }
+
+    // Move the stick towards the held keys, by at most `step` per axis
+    fn move_stick(&mut self, opposing: OpposingKeys, step: f32) {
+        let target = self.target(opposing);
+        for i in 0..2 {
+            let delta = target[i] - self.stick[i];
+            self.stick[i] = self.stick[i] + delta.max(-step).min(step);
+        }
+    }
+
+    // Keys have no noise, so the stick skips the deadzones and curve
+    fn write(&self, state: &mut ControllerState) {
+        state.set_left_stick(self.stick);
+    }
+}
+
+fn parse_key(name: &str) -> Option<Keycode> {
+    let key = Keycode::from_name(name);
+    if key.is_none() {
+        warn!("Unknown key {:?} in key map", name);
+    }
+    return key;
+}
+
+#[derive(Default)]
+#[derive(Debug)]
+pub struct ControllerState {
+    pub id: u32,
+    pub inst_id: ID,
+    pub guid: String,
+
+    pub button_x: bool,
+    pub button_y: bool,
+    pub button_a: bool,
+    pub button_b: bool,
+    pub button_l_shoulder: bool,
+    pub button_r_shoulder: bool,
+    pub button_guide: bool,
+    pub button_back: bool,
+    pub button_start: bool,
+    pub button_l_stick: bool,
+    pub button_r_stick: bool,
+    pub button_up: bool,
+    pub button_down: bool,
+    pub button_left: bool,
+    pub button_right: bool,
+
+    pub axis_l_trigger: i16,
+    pub axis_r_trigger: i16,
+    pub axis_l_x: i16,
+    pub axis_l_y: i16,
+    pub axis_r_x: i16,
+    pub axis_r_y: i16,
+
+    // Axes after deadzones and response curve. Sticks are unit vectors at most,
+    // x right and y down like the raw axes, triggers go from 0 to 1.
+    pub left_stick: [f32; 2],
+    pub right_stick: [f32; 2],
+    pub left_trigger: f32,
+    pub right_trigger: f32,
+    // Triggers pulled past the threshold
+    pub button_l_trigger: bool,
+    pub button_r_trigger: bool,
+
+    // Button masks of what went down and up during the last tick
+    pub pressed: u32,
+    pub released: u32,
+    // Edges since the last tick, so presses shorter than a tick aren't lost
+    pending_pressed: u32,
+    pending_released: u32,
+    // Buttons held at the last tick
+    last_buttons: u32,
+
+    response: Response,
+}
+
+impl InputSystem {
+    pub fn new(sdl_context: &Sdl, config: InputConfig) -> InputSystem {
+        // Initialize controller
+        let controller_subsystem = sdl_context.game_controller().unwrap();
+        let joystick_subsystem = sdl_context.joystick().unwrap();
+        let haptic_subsystem = sdl_context.haptic().unwrap();
+
+        // Enable controller events
+        if !controller_subsystem.event_state() {
+            controller_subsystem.set_event_state(true);
+        }
+
+        let mut controller_states: Vec<ControllerState> = vec![];
+        let mut event_queue = VecDeque::new();
+        let mut keymaps = vec![];
+
+        let mut keyboard_players = config.keyboard_players as usize;
+        if keyboard_players > config.keymaps.len() {
+            warn!("{} keyboard players but only {} key maps", keyboard_players, config.keymaps.len());
+            keyboard_players = config.keymaps.len();
+        }
+        if keyboard_players > KEYBOARD_COUNT as usize {
+            warn!("At most {} keyboard players are supported", KEYBOARD_COUNT);
+            keyboard_players = KEYBOARD_COUNT as usize;
+        }
+
+        for (i, keymap) in config.keymaps.iter().take(keyboard_players).enumerate() {
+            let id = KEYBOARD_ID - i as ID;
+            let mut keyboard_controller = ControllerState::default();
+            keyboard_controller.inst_id = id;
+            keyboard_controller.guid = format!("keyboard-{}", i);
+            controller_states.push(keyboard_controller);
+            keymaps.push(KeyMap::from_config(id, keymap));
+            event_queue.push_back(InputAdded(id));
+            info!("Keyboard enabled as controller {:?}.", id);
+        }
+
+        return InputSystem {
+            config: config,
+            sdl_controller_subsystem: Some(controller_subsystem),
+            sdl_joystick_subsystem: Some(joystick_subsystem),
+            open_sdl_controllers: vec![],
+            controller_states: controller_states,
+            keymaps: keymaps,
+            frame: InputFrame::default(),
+            rumble: Box::new(SdlRumble::new(haptic_subsystem)),
+            event_queue: event_queue,
+            next_virtual_id: VIRTUAL_ID,
+            event_log: None,
+        }
+    }
+
+    // Input system without SDL, only virtual controllers can be used
+    pub fn new_headless(config: InputConfig) -> InputSystem {
+        return InputSystem {
+            config: config,
+            sdl_controller_subsystem: None,
+            sdl_joystick_subsystem: None,
+            open_sdl_controllers: vec![],
+            controller_states: vec![],
+            keymaps: vec![],
+            frame: InputFrame::default(),
+            rumble: Box::new(RecordingRumble::new()),
+            event_queue: VecDeque::new(),
+            next_virtual_id: VIRTUAL_ID,
+            event_log: None,
+        }
+    }
+
+    // Register a controller whose state is written by code instead of a device
+    pub fn add_virtual_controller(&mut self) -> ID {
+        let id = self.next_virtual_id;
+        self.next_virtual_id = self.next_virtual_id - 1;
+
+        self.add_controller_with_id(id, format!("virtual{}", id));
+        info!("Virtual controller {:?} added", id);
+
+        return id;
+    }
+
+    // Stand-in for a controller of a recorded match, keeps the recorded id and GUID
+    pub fn add_replayed_controller(&mut self, id: ID, guid: String) {
+        if self.get_controller_state(id).is_some() {
+            warn!("Replayed controller {:?} added twice", id);
+            return;
+        }
+        self.add_controller_with_id(id, guid);
+        debug!("Replayed controller {:?} added", id);
+    }
+
+    fn add_controller_with_id(&mut self, id: ID, guid: String) {
+        let mut state = ControllerState::default();
+        state.inst_id = id;
+        state.guid = guid;
+        self.controller_states.push(state);
+        self.push_event(InputAdded(id));
+    }
+
+    fn push_event(&mut self, event: InputEvent) {
+        if let Some(ref mut log) = self.event_log {
+            log.push(event);
+        }
+        self.event_queue.push_back(event);
+    }
+
+    // Start logging events for a replay, events already queued are logged too
+    pub fn start_event_log(&mut self) {
+        self.event_log = Some(self.event_queue.iter().cloned().collect());
+    }
+
+    // Events queued since the last call
+    pub fn take_event_log(&mut self) -> Vec<InputEvent> {
+        return match self.event_log {
+            Some(ref mut log) => ::std::mem::replace(log, vec![]),
+            None => vec![],
+        };
+    }
+
+    pub fn remove_virtual_controller(&mut self, id: ID) {
+        let before = self.controller_states.len();
+        self.controller_states.retain(|c| c.inst_id != id);
+
+        if self.controller_states.len() != before {
+            self.push_event(InputRemoved(id));
+            info!("Virtual controller {:?} removed", id);
+        }
+    }
+
+    pub fn update(&mut self, event: Event) {
+        match event {
+            Event::ControllerButtonDown { which, button, .. } => {
+                for c in self.controller_states.iter_mut() {
+                    if c.inst_id == which {
+                        c.set_button(button, true);
+                    }
+                }
+            }
+            Event::ControllerButtonUp { which, button, .. } => {
+                for c in self.controller_states.iter_mut() {
+                    if c.inst_id == which {
+                        c.set_button(button, false);
+                    }
+                }
+            }
+            Event::ControllerAxisMotion { which, axis, value, .. } => {
+                for c in self.controller_states.iter_mut() {
+                    if c.inst_id == which {
+                        c.set_axis(axis, value);
+                        c.process();
+                    }
+                }
+            }
+            Event::ControllerDeviceAdded { which, .. } => {
+                info!("Controller {:?} Added", which);
+                let controller = match self.sdl_controller_subsystem {
+                    Some(ref subsystem) => subsystem.open(which as u32).unwrap(),
+                    None => return,
+                };
+                let guid = match self.sdl_joystick_subsystem {
+                    Some(ref joystick) => joystick.device_guid(which as u32).map(|g| g.string()).unwrap_or(String::new()),
+                    None => String::new(),
+                };
+                info!("Controller {:?} has GUID {}", which, guid);
+
+                let mut state = ControllerState::from(&controller);
+                state.response = Response::from_config(&self.config, &guid);
+                state.guid = guid;
+                state.process();
+                let id = state.inst_id;
+                self.controller_states.push(state);
+                self.open_sdl_controllers.push(controller);
+                self.rumble.attach(id, which as u32);
+
+                self.push_event(InputAdded(id));
+
+                info!("Open controllers size {:?}", self.open_sdl_controllers.len());
+                debug_controllers(&self.open_sdl_controllers);
+            }
+
+            Event::ControllerDeviceRemoved { which, .. } => {
+                info!("Controller {:?} Removed", which);
+                self.open_sdl_controllers.retain(|ref controller| which != controller.instance_id());
+                self.controller_states.retain(|ref controller_state| which != controller_state.inst_id);
+                self.rumble.detach(which);
+
+                self.push_event(InputRemoved(which));
+
+                info!("Open controllers size {:?}", self.open_sdl_controllers.len());
+                info!("Controller state size {:?}", self.controller_states.len());
+                debug_controllers(&self.open_sdl_controllers);
+            }
+            _ => {
+                self.update_keyboard(event);
+            }
+        }
+    }
+
+    fn update_keyboard(&mut self, event: Event) {
+        match event {
+            Event::KeyDown { keycode: Some(keycode), repeat: false, .. } => {
+                self.handle_key(keycode, true);
+            }
+            Event::KeyUp { keycode: Some(keycode), .. } => {
+                self.handle_key(keycode, false);
+            }
+            _ => ()
+        }
+    }
+
+    // Apply a key to every keyboard player that has it mapped
+    fn handle_key(&mut self, key: Keycode, pressed: bool) {
+        let opposing = self.config.opposing_keys;
+        let ramping = self.config.keyboard_ramp > 0.0;
+
+        for keymap in self.keymaps.iter_mut() {
+            let state = match self.controller_states.iter_mut().filter(|c| c.inst_id == keymap.id).next() {
+                Some(state) => state,
+                None => continue,
+            };
+
+            if keymap.axes.iter().any(|&(k, _, _)| k == key) {
+                keymap.held.retain(|&k| k != key);
+                if pressed {
+                    keymap.held.push(key);
+                }
+                // Ramped sticks only move in tick()
+                if !ramping {
+                    keymap.move_stick(opposing, 1.0);
+                    keymap.write(state);
+                }
+            }
+            for &(_, button) in keymap.buttons.iter().filter(|&&(k, _)| k == key) {
+                state.set_button(button, pressed);
+            }
+        }
+    }
+
+    // Advance time based input by one simulation step and freeze the frame games see.
+    // Call right before the step, after bots and scripts wrote their controllers.
+    pub fn tick(&mut self, dt: f32) {
+        if self.config.keyboard_ramp > 0.0 {
+            let opposing = self.config.opposing_keys;
+            let step = dt / self.config.keyboard_ramp;
+
+            for keymap in self.keymaps.iter_mut() {
+                if let Some(state) = self.controller_states.iter_mut().filter(|c| c.inst_id == keymap.id).next() {
+                    keymap.move_stick(opposing, step);
+                    keymap.write(state);
+                }
+            }
+        }
+
+        for state in self.controller_states.iter_mut() {
+            state.freeze_edges();
+        }
+
+        self.frame = InputFrame {
+            tick: self.frame.tick + 1,
+            controllers: self.controller_states.iter().map(ControllerFrame::from_state).collect(),
+        };
+    }
+
+    // Rumble a controller, strength from 0 to 1. Keyboards can't rumble and are skipped.
+    pub fn rumble(&mut self, id: ID, strength: f32, duration_ms: u32) {
+        if is_keyboard_id(id) {
+            return;
+        }
+        self.rumble.rumble(id, strength.max(0.0).min(1.0), duration_ms);
+    }
+
+    pub fn set_rumble_backend(&mut self, backend: Box<RumbleBackend>) {
+        self.rumble = backend;
+    }
+
+    pub fn frame(&self) -> &InputFrame {
+        return &self.frame;
+    }
+
+    pub fn just_pressed(&self, id: ID, button: Button) -> bool {
+        return self.frame.just_pressed(id, button);
+    }
+
+    pub fn just_released(&self, id: ID, button: Button) -> bool {
+        return self.frame.just_released(id, button);
+    }
+
+    pub fn controller_ids(&self) -> Vec<ID> {
+        return self.controller_states.iter().map(|c| c.inst_id).collect();
+    }
+
+    pub fn get_controller_state(&self, id: ID) -> Option<&ControllerState> {
+        return self.controller_states.iter().filter(|c| c.inst_id == id).next();
+    }
+
+    pub fn get_controller_state_mut(&mut self, id: ID) -> Option<&mut ControllerState> {
+        return self.controller_states.iter_mut().filter(|c| c.inst_id == id).next();
+    }
+
+    pub fn event(&mut self) -> Option<InputEvent> {
+        return self.event_queue.pop_front();
+    }
+}
+
+
+impl ControllerState {
+    fn set_button(&mut self, button: Button, value: bool) {
+        let bit = button_bit(button);
+        if value && self.buttons() & bit == 0 {
+            self.pending_pressed = self.pending_pressed | bit;
+        } else if !value && self.buttons() & bit != 0 {
+            self.pending_released = self.pending_released | bit;
+        }
+
+        match button {
+            Button::X => { self.button_x = value }
+            Button::Y => { self.button_y = value }
+            Button::A => { self.button_a = value }
+            Button::B => { self.button_b = value }
+            Button::LeftShoulder => { self.button_l_shoulder = value }
+            Button::RightShoulder => { self.button_r_shoulder = value }
+            Button::Guide => { self.button_guide = value }
+            Button::Back => { self.button_back = value }
+            Button::Start => { self.button_start = value }
+            Button::LeftStick => { self.button_l_stick = value }
+            Button::RightStick => { self.button_r_stick = value }
+            Button::DPadUp => { self.button_up = value }
+            Button::DPadDown => { self.button_down = value }
+            Button::DPadLeft => { self.button_left = value }
+            Button::DPadRight => { self.button_right = value }
+        }
+    }
+
+    // Held buttons as a mask of button_bit()s
+    pub fn buttons(&self) -> u32 {
+        let buttons = [(Button::X, self.button_x),
+                       (Button::Y, self.button_y),
+                       (Button::A, self.button_a),
+                       (Button::B, self.button_b),
+                       (Button::LeftShoulder, self.button_l_shoulder),
+                       (Button::RightShoulder, self.button_r_shoulder),
+                       (Button::Guide, self.button_guide),
+                       (Button::Back, self.button_back),
+                       (Button::Start, self.button_start),
+                       (Button::LeftStick, self.button_l_stick),
+                       (Button::RightStick, self.button_r_stick),
+                       (Button::DPadUp, self.button_up),
+                       (Button::DPadDown, self.button_down),
+                       (Button::DPadLeft, self.button_left),
+                       (Button::DPadRight, self.button_right)];
+
+        let mut mask = 0;
+        for &(button, held) in buttons.iter() {
+            if held {
+                mask = mask | button_bit(button);
+            }
+        }
+        if self.button_l_trigger {
+            mask = mask | TRIGGER_LEFT_BIT;
+        }
+        if self.button_r_trigger {
+            mask = mask | TRIGGER_RIGHT_BIT;
+        }
+        return mask;
+    }
+
+    // Set every button from a mask without recording edges, see replay_edges
+    pub fn set_buttons(&mut self, mask: u32) {
+        for &button in [Button::X, Button::Y, Button::A, Button::B, Button::LeftShoulder,
+                        Button::RightShoulder, Button::Guide, Button::Back, Button::Start,
+                        Button::LeftStick, Button::RightStick, Button::DPadUp, Button::DPadDown,
+                        Button::DPadLeft, Button::DPadRight].iter() {
+            self.set_button(button, mask & button_bit(button) != 0);
+        }
+        self.button_l_trigger = mask & TRIGGER_LEFT_BIT != 0;
+        self.button_r_trigger = mask & TRIGGER_RIGHT_BIT != 0;
+        self.pending_pressed = 0;
+        self.pending_released = 0;
+    }
+
+    // Edges a recorded tick had, the next tick() reports them again
+    pub fn replay_edges(&mut self, pressed: u32, released: u32) {
+        self.pending_pressed = pressed;
+        self.pending_released = released;
+    }
+
+    pub fn just_pressed(&self, button: Button) -> bool {
+        return self.pressed & button_bit(button) != 0;
+    }
+
+    pub fn just_released(&self, button: Button) -> bool {
+        return self.released & button_bit(button) != 0;
+    }
+
+    // Buttons written directly, by bots or replays, count as pressed when they differ
+    // from the last tick. Edges reported by events are added on top.
+    fn freeze_edges(&mut self) {
+        let buttons = self.buttons();
+        self.pressed = self.pending_pressed | (buttons & !self.last_buttons);
+        self.released = self.pending_released | (self.last_buttons & !buttons);
+        self.pending_pressed = 0;
+        self.pending_released = 0;
+        self.last_buttons = buttons;
+    }
+
+    // For controllers driven by code: sets the stick as games see it, the raw axes follow
+    pub fn set_left_stick(&mut self, stick: [f32; 2]) {
+        self.left_stick = stick;
+        self.axis_l_x = (stick[0] * std::i16::MAX as f32) as i16;
+        self.axis_l_y = (stick[1] * std::i16::MAX as f32) as i16;
+    }
+
+    // Update the processed axes from the raw ones
+    fn process(&mut self) {
+        self.left_stick = self.response.stick(self.axis_l_x, self.axis_l_y);
+        self.right_stick = self.response.stick(self.axis_r_x, self.axis_r_y);
+        self.left_trigger = self.response.trigger(self.axis_l_trigger);
+        self.right_trigger = self.response.trigger(self.axis_r_trigger);
+        self.button_l_trigger = self.left_trigger >= self.response.trigger_threshold;
+        self.button_r_trigger = self.right_trigger >= self.response.trigger_threshold;
+    }
+
+    fn set_axis(&mut self, axis: Axis, value: i16) {
+        match axis {
+            Axis::TriggerLeft => { self.axis_l_trigger = value }
+            Axis::TriggerRight => { self.axis_r_trigger = value }
+            Axis::LeftX => { self.axis_l_x = value }
+            Axis::LeftY => { self.axis_l_y = value }
+            Axis::RightX => { self.axis_r_x = value }
+            Axis::RightY => { self.axis_r_y = value }
+        }
+    }
+}
+
+impl<'a> From<&'a GameController> for ControllerState {
+    fn from(controller: &'a GameController) -> Self {
+        let mut state = ControllerState::default();
+
+        state.inst_id = controller.instance_id();
+
+        state.set_button(Button::X, controller.button(Button::X));
+        state.set_button(Button::Y, controller.button(Button::Y));
+        state.set_button(Button::A, controller.button(Button::A));
+        state.set_button(Button::B, controller.button(Button::B));
+        state.set_button(Button::LeftShoulder, controller.button(Button::LeftShoulder));
+        state.set_button(Button::RightShoulder, controller.button(Button::RightShoulder));
+        state.set_button(Button::Guide, controller.button(Button::Guide));
+        state.set_button(Button::Back, controller.button(Button::Back));
+        state.set_button(Button::Start, controller.button(Button::Start));
+        state.set_button(Button::LeftStick, controller.button(Button::LeftStick));
+        state.set_button(Button::RightStick, controller.button(Button::RightStick));
+        state.set_button(Button::DPadUp, controller.button(Button::DPadUp));
+        state.set_button(Button::DPadDown, controller.button(Button::DPadDown));
+        state.set_button(Button::DPadLeft, controller.button(Button::DPadLeft));
+        state.set_button(Button::DPadRight, controller.button(Button::DPadRight));
+
+        return state;
+
+        //Avoid setting axes because we don't have deadzone information here
+        //
+        //        state.set_axis(Axis::TriggerLeft, controller.axis(Axis::TriggerLeft));
+        //        state.set_axis(Axis::TriggerRight, controller.axis(Axis::TriggerRight));
+        //        state.set_axis(Axis::LeftX, controller.axis(Axis::LeftX));
+        //        state.set_axis(Axis::LeftY, controller.axis(Axis::LeftY));
+        //        state.set_axis(Axis::RightX, controller.axis(Axis::RightX));
+        //        state.set_axis(Axis::RightY, controller.axis(Axis::RightY));
+        //
+        //        return state;
+    }
+}
+
+fn debug_controllers(controllers: &Vec<GameController>) {
+    for ref c in controllers {
+        debug!("controller {:?}", c.instance_id());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f32 = 0.001;
+
+    fn keymap(held: Vec<Keycode>) -> KeyMap {
+        KeyMap {
+            id: KEYBOARD_ID,
+            axes: vec![(Keycode::W, Axis::LeftY, -1),
+                       (Keycode::S, Axis::LeftY, 1),
+                       (Keycode::A, Axis::LeftX, -1),
+                       (Keycode::D, Axis::LeftX, 1)],
+            buttons: vec![],
+            held: held,
+            stick: [0.0, 0.0],
+        }
+    }
+
+    fn assert_near(actual: [f32; 2], expected: [f32; 2]) {
+        assert!((actual[0] - expected[0]).abs() < EPSILON && (actual[1] - expected[1]).abs() < EPSILON,
+                "{:?} != {:?}", actual, expected);
+    }
+
+    #[test]
+    fn last_opposing_key_wins() {
+        assert_near(keymap(vec![Keycode::A, Keycode::D]).target(OpposingKeys::Last), [1.0, 0.0]);
+        assert_near(keymap(vec![Keycode::D, Keycode::A]).target(OpposingKeys::Last), [-1.0, 0.0]);
+        assert_near(keymap(vec![Keycode::S, Keycode::W]).target(OpposingKeys::Last), [0.0, -1.0]);
+    }
+
+    #[test]
+    fn opposing_keys_cancel() {
+        assert_near(keymap(vec![Keycode::A, Keycode::D]).target(OpposingKeys::Cancel), [0.0, 0.0]);
+        assert_near(keymap(vec![Keycode::W, Keycode::S, Keycode::D]).target(OpposingKeys::Cancel), [1.0, 0.0]);
+    }
+
+    #[test]
+    fn diagonal_is_normalized() {
+        let half_sqrt2 = 0.5f32.sqrt();
+
+        assert_near(keymap(vec![Keycode::W, Keycode::D]).target(OpposingKeys::Last), [half_sqrt2, -half_sqrt2]);
+        assert_near(keymap(vec![Keycode::A, Keycode::D, Keycode::S]).target(OpposingKeys::Last),
+                    [half_sqrt2, half_sqrt2]);
+    }
+
+    #[test]
+    fn stick_moves_by_step() {
+        let mut keymap = keymap(vec![Keycode::D]);
+
+        keymap.move_stick(OpposingKeys::Last, 0.4);
+        assert_near(keymap.stick, [0.4, 0.0]);
+        keymap.move_stick(OpposingKeys::Last, 0.4);
+        keymap.move_stick(OpposingKeys::Last, 0.4);
+        assert_near(keymap.stick, [1.0, 0.0]);
+
+        keymap.held.clear();
+        keymap.move_stick(OpposingKeys::Last, 0.4);
+        assert_near(keymap.stick, [0.6, 0.0]);
+    }
+}
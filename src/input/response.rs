@@ -0,0 +1,183 @@
+use std;
+
+use config::InputConfig;
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ResponseCurve {
+    Linear,
+    // Finer control near the center
+    Quadratic,
+    // Piecewise linear through curve_points
+    Custom,
+}
+
+// How raw stick and trigger values turn into what games see
+#[derive(Debug, Clone)]
+pub struct Response {
+    // Fraction of full deflection treated as centered
+    deadzone: f32,
+    // Fraction of full deflection at the edge treated as fully pushed
+    outer_deadzone: f32,
+    curve: ResponseCurve,
+    // Outputs for evenly spaced inputs from 0 to 1
+    curve_points: Vec<f32>,
+    // Processed trigger value from which the trigger counts as pressed
+    pub trigger_threshold: f32,
+}
+
+impl Default for Response {
+    fn default() -> Response {
+        Response {
+            deadzone: 0.0,
+            outer_deadzone: 0.0,
+            curve: ResponseCurve::Linear,
+            curve_points: vec![],
+            trigger_threshold: 0.5,
+        }
+    }
+}
+
+impl Response {
+    // Settings from [input], with the overrides of the controller with this guid applied
+    pub fn from_config(config: &InputConfig, guid: &str) -> Response {
+        let mut response = Response {
+            deadzone: config.deadzone,
+            outer_deadzone: config.outer_deadzone,
+            curve: config.curve,
+            curve_points: config.curve_points.clone(),
+            trigger_threshold: config.trigger_threshold,
+        };
+
+        for controller in config.controllers.iter().filter(|c| c.guid == guid) {
+            if let Some(deadzone) = controller.deadzone {
+                response.deadzone = deadzone;
+            }
+            if let Some(outer_deadzone) = controller.outer_deadzone {
+                response.outer_deadzone = outer_deadzone;
+            }
+            if let Some(curve) = controller.curve {
+                response.curve = curve;
+            }
+            if let Some(ref points) = controller.curve_points {
+                response.curve_points = points.clone();
+            }
+            if let Some(threshold) = controller.trigger_threshold {
+                response.trigger_threshold = threshold;
+            }
+        }
+
+        if response.curve == ResponseCurve::Custom && response.curve_points.len() < 2 {
+            warn!("Custom response curve needs at least 2 points, using linear");
+            response.curve = ResponseCurve::Linear;
+        }
+
+        return response;
+    }
+
+    // Radial deadzone: the direction is kept and the length rescaled,
+    // so the output starts at 0 right outside the deadzone
+    pub fn stick(&self, x: i16, y: i16) -> [f32; 2] {
+        let raw = [normalize(x), normalize(y)];
+        let length = (raw[0] * raw[0] + raw[1] * raw[1]).sqrt();
+        if length == 0.0 {
+            return [0.0, 0.0];
+        }
+
+        let scaled = self.apply(length.min(1.0));
+        return [raw[0] / length * scaled, raw[1] / length * scaled];
+    }
+
+    // Triggers rest at 0 and go up to 1
+    pub fn trigger(&self, value: i16) -> f32 {
+        return self.apply(normalize(value).max(0.0));
+    }
+
+    fn apply(&self, value: f32) -> f32 {
+        let live = 1.0 - self.outer_deadzone - self.deadzone;
+        if value <= self.deadzone || live <= 0.0 {
+            return 0.0;
+        }
+        let value = ((value - self.deadzone) / live).min(1.0);
+
+        return match self.curve {
+            ResponseCurve::Linear => value,
+            ResponseCurve::Quadratic => value * value,
+            ResponseCurve::Custom => {
+                let segments = (self.curve_points.len() - 1) as f32;
+                let position = value * segments;
+                let i = (position.floor() as usize).min(self.curve_points.len() - 2);
+                let t = position - i as f32;
+                self.curve_points[i] + (self.curve_points[i + 1] - self.curve_points[i]) * t
+            }
+        };
+    }
+}
+
+// -32768 would be slightly more than full
+fn normalize(value: i16) -> f32 {
+    return (value as f32 / std::i16::MAX as f32).max(-1.0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f32 = 0.001;
+
+    fn response(deadzone: f32, outer_deadzone: f32) -> Response {
+        Response {
+            deadzone: deadzone,
+            outer_deadzone: outer_deadzone,
+            ..Response::default()
+        }
+    }
+
+    fn raw(value: f32) -> i16 {
+        return (value * std::i16::MAX as f32).round() as i16;
+    }
+
+    fn assert_near(actual: [f32; 2], expected: [f32; 2]) {
+        assert!((actual[0] - expected[0]).abs() < EPSILON && (actual[1] - expected[1]).abs() < EPSILON,
+                "{:?} != {:?}", actual, expected);
+    }
+
+    #[test]
+    fn stick_inside_deadzone_is_centered() {
+        let response = response(0.2, 0.0);
+
+        assert_near(response.stick(raw(0.1), raw(-0.1)), [0.0, 0.0]);
+        assert_near(response.stick(0, 0), [0.0, 0.0]);
+    }
+
+    #[test]
+    fn stick_deadzone_is_radial() {
+        let response = response(0.2, 0.0);
+
+        // Each axis alone is inside the deadzone, but the length is not
+        let diagonal = response.stick(raw(0.15), raw(0.15));
+        assert!(diagonal[0] > 0.0 && diagonal[1] > 0.0);
+
+        // Length 0.5 rescales to (0.5 - 0.2) / 0.8, direction is kept
+        assert_near(response.stick(raw(0.3), raw(0.4)), [0.6 * 0.375, 0.8 * 0.375]);
+    }
+
+    #[test]
+    fn stick_rescales_from_deadzone_edge() {
+        let response = response(0.2, 0.0);
+
+        assert_near(response.stick(raw(0.2), 0), [0.0, 0.0]);
+        assert_near(response.stick(raw(0.6), 0), [0.5, 0.0]);
+        assert_near(response.stick(std::i16::MAX, 0), [1.0, 0.0]);
+        assert_near(response.stick(0, std::i16::MIN), [0.0, -1.0]);
+    }
+
+    #[test]
+    fn stick_outer_deadzone_reaches_full() {
+        let response = response(0.1, 0.1);
+
+        assert_near(response.stick(raw(0.5), 0), [0.5, 0.0]);
+        assert_near(response.stick(raw(0.9), 0), [1.0, 0.0]);
+        assert_near(response.stick(raw(-0.95), 0), [-1.0, 0.0]);
+    }
+}
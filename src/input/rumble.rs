@@ -0,0 +1,89 @@
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use sdl2::haptic::Haptic;
+use sdl2::HapticSubsystem;
+
+use super::ID;
+
+// Where rumble requests end up
+pub trait RumbleBackend {
+    // A controller was connected, `device_index` is its SDL joystick device index
+    fn attach(&mut self, id: ID, device_index: u32);
+    fn detach(&mut self, id: ID);
+    // Strength from 0 to 1
+    fn rumble(&mut self, id: ID, strength: f32, duration_ms: u32);
+}
+
+// Rumbles controllers through SDL haptics, controllers without rumble are skipped
+pub struct SdlRumble {
+    subsystem: HapticSubsystem,
+    haptics: Vec<(ID, Haptic)>,
+}
+
+impl SdlRumble {
+    pub fn new(subsystem: HapticSubsystem) -> SdlRumble {
+        SdlRumble {
+            subsystem: subsystem,
+            haptics: vec![],
+        }
+    }
+}
+
+impl RumbleBackend for SdlRumble {
+    fn attach(&mut self, id: ID, device_index: u32) {
+        match self.subsystem.open_from_joystick_id(device_index) {
+            Ok(haptic) => self.haptics.push((id, haptic)),
+            Err(err) => info!("Controller {:?} has no rumble: {:?}", id, err),
+        }
+    }
+
+    fn detach(&mut self, id: ID) {
+        self.haptics.retain(|&(haptic_id, _)| haptic_id != id);
+    }
+
+    fn rumble(&mut self, id: ID, strength: f32, duration_ms: u32) {
+        for &mut (haptic_id, ref mut haptic) in self.haptics.iter_mut() {
+            if haptic_id == id {
+                haptic.rumble_play(strength, duration_ms);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RumbleRequest {
+    pub id: ID,
+    pub strength: f32,
+    pub duration_ms: u32,
+}
+
+// Doesn't rumble anything, only remembers the requests. For headless runs and tests.
+pub struct RecordingRumble {
+    log: Rc<RefCell<Vec<RumbleRequest>>>,
+}
+
+impl RecordingRumble {
+    pub fn new() -> RecordingRumble {
+        RecordingRumble { log: Rc::new(RefCell::new(vec![])) }
+    }
+
+    // Shared with the backend, stays readable after the backend is handed to the InputSystem
+    pub fn log(&self) -> Rc<RefCell<Vec<RumbleRequest>>> {
+        return self.log.clone();
+    }
+}
+
+impl RumbleBackend for RecordingRumble {
+    fn attach(&mut self, _id: ID, _device_index: u32) {}
+
+    fn detach(&mut self, _id: ID) {}
+
+    fn rumble(&mut self, id: ID, strength: f32, duration_ms: u32) {
+        self.log.borrow_mut().push(RumbleRequest {
+            id: id,
+            strength: strength,
+            duration_ms: duration_ms,
+        });
+    }
+}
@@ -5,6 +5,9 @@ extern crate env_logger;
 #[macro_use]
 extern crate serde_derive;
 
+#[macro_use]
+extern crate bitflags;
+
 #[macro_use]
 extern crate gfx;
 extern crate gfx_core;
@@ -23,20 +26,28 @@ mod input;
 mod game;
 mod draw;
 mod physics;
+mod headless;
+mod timestep;
+mod rng;
+mod replay;
 
 use input::InputSystem;
 
 use draw::DrawSystem;
 use physics::PhysicsSystem;
+use timestep::FixedTimestep;
+use replay::{ReplayOptions, ReplayRecorder};
+use game::bot::Bots;
 
-use game::minigame::MiniGame;
-use game::minigames::sumo::Sumo;
-// use game::minigames::triangle_buffers::Triangle;
+use game::minigame::MiniGameSettings;
+use game::registry::MiniGameRegistry;
+use game::session::Session;
 
 use sdl2::event::Event;
 use sdl2::event::WindowEvent;
 use sdl2::keyboard::Keycode;
 use std::time::Duration;
+use std::env;
 
 pub fn main() {
     // Initialize logging
@@ -49,6 +60,17 @@ pub fn main() {
         Err(err) => panic!("{:?}", err),
     };
 
+    // Play back a recorded match
+    if let Some(options) = ReplayOptions::from_args(env::args()) {
+        replay::run(config, options);
+        return;
+    }
+
+    // Run without a window when asked to, e.g. on CI
+    if let Some(options) = headless::HeadlessOptions::from_args(env::args()) {
+        headless::run(config, options);
+        return;
+    }
 
     let sdl_context = sdl2::init().unwrap();
 
@@ -56,15 +78,25 @@ pub fn main() {
     let mut draw_system = DrawSystem::new(&sdl_context, &mut config.video);
 
     // Init Physics system
-    let mut physics_system = PhysicsSystem::new();
+    let mut timestep = FixedTimestep::new(&config.simulation);
+    let mut physics_system = PhysicsSystem::new(timestep.dt());
 
     // Init Input system
     let mut input_system = InputSystem::new(&sdl_context, config.input);
 
 
-    // The active minigame
-    // let mut minigame : Triangle = MiniGame::new();
-    let mut minigame : Sumo = MiniGame::new(&mut draw_system, &mut physics_system, & input_system);
+    let seed = rng::seed_from_time();
+
+    // Bots join before recording starts so replays see them like any other controller
+    let mut bots = Bots::from_config(&config.bots, &mut input_system, seed);
+
+    let settings = MiniGameSettings { avoid_the_wall: config.avoid_the_wall };
+
+    let mut recorder = replay::record_path_from_args(env::args())
+        .map(|path| ReplayRecorder::new(path, seed, timestep.dt(), &config.session.minigames, &settings, &mut input_system));
+
+    // The minigames to play, in order
+    let mut session = Session::new(MiniGameRegistry::with_defaults(settings), config.session.minigames, seed, &draw_system);
 
     // Event loop
     let mut event_pump = sdl_context.event_pump().unwrap();
@@ -98,16 +130,30 @@ pub fn main() {
                 }
             }
         }
-        ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 60));
+        for _ in 0..timestep.advance() {
+            bots.update(&session, &physics_system, &mut input_system);
+            input_system.tick(timestep.dt());
+            if let Some(ref mut recorder) = recorder {
+                recorder.record_tick(&mut input_system);
+            }
+            session.step(&mut draw_system, &mut physics_system, &mut input_system);
+        }
 
         draw_system.pre_render();
 
-        minigame.step(&mut draw_system, &mut physics_system, &mut input_system);
-        minigame.render(&mut draw_system);
+        session.render(&mut draw_system, &physics_system, timestep.alpha());
 
         draw_system.post_render();
 
+        // Don't spin a whole core rendering frames nobody will see
+        ::std::thread::sleep(Duration::new(0, 1_000_000));
     }
+
+    if let Some(recorder) = recorder {
+        recorder.finish(&physics_system);
+    }
+
+    session.finish_current(&mut physics_system);
 }
 
 //enum Component {
@@ -0,0 +1,243 @@
+use super::wrapped2d::b2;
+use super::{GameUserData, BodyHandle, SIZE_FACTOR, world_to_physics};
+
+use game::minigame::Point as WorldPoint;
+
+// Identifies a joint created by the PhysicsSystem
+pub type JointId = u32;
+
+// Motor driving a joint towards a speed (radians or world units per second)
+#[derive(Debug, Clone, Copy)]
+pub struct JointMotor {
+    pub speed: f32,
+    // Max torque for revolute and wheel joints, max force for prismatic ones, in physics units
+    pub max_effort: f32,
+}
+
+// Anchors and axes are in world coordinates at the time the joint is created
+#[derive(Debug, Clone, Copy)]
+pub enum JointKind {
+    // Keeps the anchors at their current distance, springy when frequency > 0
+    Distance { anchor_a: WorldPoint, anchor_b: WorldPoint, frequency: f32, damping_ratio: f32 },
+    // Hinge, limits are angles relative to the current angle
+    Revolute { anchor: WorldPoint, limits: Option<(f32, f32)>, motor: Option<JointMotor> },
+    // Slides along the axis, limits are translations along it
+    Prismatic { anchor: WorldPoint, axis: WorldPoint, limits: Option<(f32, f32)>, motor: Option<JointMotor> },
+    // Wheel on a suspension spring along the axis
+    Wheel { anchor: WorldPoint, axis: WorldPoint, frequency: f32, damping_ratio: f32, motor: Option<JointMotor> },
+    // Glues the bodies together, soft when frequency > 0
+    Weld { anchor: WorldPoint, frequency: f32, damping_ratio: f32 },
+    // Anchors can't get further apart than max_length, but may come closer
+    Rope { anchor_a: WorldPoint, anchor_b: WorldPoint, max_length: f32 },
+    Pulley { ground_a: WorldPoint, ground_b: WorldPoint, anchor_a: WorldPoint, anchor_b: WorldPoint, ratio: f32 },
+    // Couples two revolute or prismatic joints
+    Gear { joint_a: JointId, joint_b: JointId, ratio: f32 },
+    // Drives body b towards its current offset from body a
+    Motor { max_force: f32, max_torque: f32, correction_factor: f32 },
+    // Top down friction, body a is usually the ground
+    Friction { anchor: WorldPoint, max_force: f32, max_torque: f32 },
+    // Pulls body b towards a target point, body a is usually the ground
+    Mouse { target: WorldPoint, max_force: f32, frequency: f32, damping_ratio: f32 },
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct JointSpec {
+    pub kind: JointKind,
+    pub collide_connected: bool,
+    // The joint is destroyed after a step where its reaction exceeds these, in physics units
+    pub break_force: Option<f32>,
+    pub break_torque: Option<f32>,
+}
+
+impl JointSpec {
+    pub fn new(kind: JointKind) -> JointSpec {
+        JointSpec {
+            kind: kind,
+            collide_connected: false,
+            break_force: None,
+            break_torque: None,
+        }
+    }
+
+    pub fn colliding(mut self) -> JointSpec {
+        self.collide_connected = true;
+        return self;
+    }
+
+    pub fn breaking_above(mut self, force: f32) -> JointSpec {
+        self.break_force = Some(force);
+        return self;
+    }
+
+    pub fn breaking_above_torque(mut self, torque: f32) -> JointSpec {
+        self.break_torque = Some(torque);
+        return self;
+    }
+}
+
+pub struct JointComponent {
+    id: JointId,
+}
+
+impl JointComponent {
+    pub fn new(id: JointId) -> JointComponent {
+        JointComponent { id: id }
+    }
+
+    pub fn id(&self) -> JointId {
+        return self.id;
+    }
+}
+
+// A live joint and the bodies it connects, Box2D destroys it together with either body.
+// The bodies are read back from the Box2D joint, a gear connects the second bodies of its joints.
+pub struct JointRecord {
+    pub handle: b2::JointHandle,
+    pub body_a: BodyHandle,
+    pub body_b: BodyHandle,
+    // The joints a gear couples, it has to go before either of them
+    pub coupled: Option<(JointId, JointId)>,
+    pub break_force: Option<f32>,
+    pub break_torque: Option<f32>,
+}
+
+// Create the Box2D joint for a spec. Gear joints need the handles of the joints they couple.
+pub fn create_b2_joint(world: &mut b2::World<GameUserData>,
+                       body_a: BodyHandle,
+                       body_b: BodyHandle,
+                       spec: &JointSpec,
+                       gear_joints: Option<(b2::JointHandle, b2::JointHandle)>,
+                       id: JointId)
+                       -> b2::JointHandle {
+    let collide_connected = spec.collide_connected;
+
+    return match spec.kind {
+        JointKind::Distance { anchor_a, anchor_b, frequency, damping_ratio } => {
+            let mut def = b2::DistanceJointDef::new(body_a, body_b);
+            def.init(world, body_a, body_b, &world_to_physics(&anchor_a), &world_to_physics(&anchor_b));
+            def.frequency = frequency;
+            def.damping_ratio = damping_ratio;
+            def.collide_connected = collide_connected;
+            world.create_joint_with(&def, id)
+        }
+        JointKind::Revolute { anchor, limits, motor } => {
+            let mut def = b2::RevoluteJointDef::new(body_a, body_b);
+            def.init(world, body_a, body_b, &world_to_physics(&anchor));
+            if let Some((lower, upper)) = limits {
+                def.enable_limit = true;
+                def.lower_angle = lower;
+                def.upper_angle = upper;
+            }
+            if let Some(motor) = motor {
+                def.enable_motor = true;
+                def.motor_speed = motor.speed;
+                def.max_motor_torque = motor.max_effort;
+            }
+            def.collide_connected = collide_connected;
+            world.create_joint_with(&def, id)
+        }
+        JointKind::Prismatic { anchor, axis, limits, motor } => {
+            let mut def = b2::PrismaticJointDef::new(body_a, body_b);
+            def.init(world, body_a, body_b, &world_to_physics(&anchor), &unit_axis(&axis));
+            if let Some((lower, upper)) = limits {
+                def.enable_limit = true;
+                def.lower_translation = lower * SIZE_FACTOR;
+                def.upper_translation = upper * SIZE_FACTOR;
+            }
+            if let Some(motor) = motor {
+                def.enable_motor = true;
+                def.motor_speed = motor.speed * SIZE_FACTOR;
+                def.max_motor_force = motor.max_effort;
+            }
+            def.collide_connected = collide_connected;
+            world.create_joint_with(&def, id)
+        }
+        JointKind::Wheel { anchor, axis, frequency, damping_ratio, motor } => {
+            let mut def = b2::WheelJointDef::new(body_a, body_b);
+            def.init(world, body_a, body_b, &world_to_physics(&anchor), &unit_axis(&axis));
+            def.frequency = frequency;
+            def.damping_ratio = damping_ratio;
+            if let Some(motor) = motor {
+                def.enable_motor = true;
+                def.motor_speed = motor.speed;
+                def.max_motor_torque = motor.max_effort;
+            }
+            def.collide_connected = collide_connected;
+            world.create_joint_with(&def, id)
+        }
+        JointKind::Weld { anchor, frequency, damping_ratio } => {
+            let mut def = b2::WeldJointDef::new(body_a, body_b);
+            def.init(world, body_a, body_b, &world_to_physics(&anchor));
+            def.frequency = frequency;
+            def.damping_ratio = damping_ratio;
+            def.collide_connected = collide_connected;
+            world.create_joint_with(&def, id)
+        }
+        JointKind::Rope { anchor_a, anchor_b, max_length } => {
+            let mut def = b2::RopeJointDef::new(body_a, body_b);
+            def.local_anchor_a = world.body(body_a).local_point(&world_to_physics(&anchor_a));
+            def.local_anchor_b = world.body(body_b).local_point(&world_to_physics(&anchor_b));
+            def.max_length = max_length * SIZE_FACTOR;
+            def.collide_connected = collide_connected;
+            world.create_joint_with(&def, id)
+        }
+        JointKind::Pulley { ground_a, ground_b, anchor_a, anchor_b, ratio } => {
+            let mut def = b2::PulleyJointDef::new(body_a, body_b);
+            def.init(body_a,
+                     body_b,
+                     world_to_physics(&ground_a),
+                     world_to_physics(&ground_b),
+                     &world_to_physics(&anchor_a),
+                     &world_to_physics(&anchor_b),
+                     ratio);
+            def.local_anchor_a = world.body(body_a).local_point(&world_to_physics(&anchor_a));
+            def.local_anchor_b = world.body(body_b).local_point(&world_to_physics(&anchor_b));
+            def.collide_connected = collide_connected;
+            world.create_joint_with(&def, id)
+        }
+        JointKind::Gear { ratio, .. } => {
+            let (joint_a, joint_b) = gear_joints.expect("Gear joint needs two existing joints");
+            let mut def = b2::GearJointDef::new(joint_a, joint_b);
+            def.ratio = ratio;
+            def.collide_connected = collide_connected;
+            world.create_joint_with(&def, id)
+        }
+        JointKind::Motor { max_force, max_torque, correction_factor } => {
+            let mut def = b2::MotorJointDef::new(body_a, body_b);
+            def.init(world, body_a, body_b);
+            def.max_force = max_force;
+            def.max_torque = max_torque;
+            def.correction_factor = correction_factor;
+            def.collide_connected = collide_connected;
+            world.create_joint_with(&def, id)
+        }
+        JointKind::Friction { anchor, max_force, max_torque } => {
+            let mut def = b2::FrictionJointDef::new(body_a, body_b);
+            def.init(world, body_a, body_b, &world_to_physics(&anchor));
+            def.max_force = max_force;
+            def.max_torque = max_torque;
+            def.collide_connected = collide_connected;
+            world.create_joint_with(&def, id)
+        }
+        JointKind::Mouse { target, max_force, frequency, damping_ratio } => {
+            let mut def = b2::MouseJointDef::new(body_a, body_b);
+            def.target = world_to_physics(&target);
+            def.max_force = max_force;
+            def.frequency = frequency;
+            def.damping_ratio = damping_ratio;
+            def.collide_connected = collide_connected;
+            world.create_joint_with(&def, id)
+        }
+    };
+}
+
+fn unit_axis(axis: &WorldPoint) -> b2::Vec2 {
+    let length = (axis[0] * axis[0] + axis[1] * axis[1]).sqrt();
+    if length == 0.0 {
+        return b2::Vec2 { x: 1.0, y: 0.0 };
+    }
+    return b2::Vec2 {
+        x: axis[0] / length,
+        y: axis[1] / length,
+    };
+}
@@ -0,0 +1,1319 @@
+extern crate wrapped2d;
+
+pub mod spec;
+mod joint;
+mod snapshot;
+
+use self::wrapped2d::b2;
+use self::wrapped2d::handle::TypedHandle;
+use self::wrapped2d::user_data::{UserDataTypes, UserData};
+use self::wrapped2d::collision::shapes::chain::ChainShape;
+use self::wrapped2d::collision::shapes::circle::CircleShape;
+use self::wrapped2d::collision::shapes::edge::EdgeShape;
+use self::wrapped2d::dynamics::world::callbacks::{ContactAccess, BodyAccess, FixtureAccess};
+
+use game::minigame::Point as WorldPoint;
+
+pub use self::spec::{BodySpec, BodySpecs, BodyKind, ShapeSpec};
+pub use self::joint::{JointId, JointKind, JointSpec, JointMotor, JointComponent};
+use self::joint::{JointRecord, create_b2_joint};
+pub use self::snapshot::PhysicsSnapshot;
+use self::snapshot::{BodyEntry, JointEntry};
+use self::wrapped2d::serialize::{BodySnapshot, FixtureSnapshot, JointSnapshot, IdToHandle};
+use self::wrapped2d::b2::Joint;
+
+use stl;
+use std::f32::consts::PI;
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::mem;
+
+pub type B2Point = b2::Vec2;
+
+// Identifies the game entity a physics body belongs to
+pub type EntityId = u32;
+
+// What kind of game object a body is, so games can react to collisions without tracking ids
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Category {
+    Unknown,
+    Player,
+    Wall,
+    Boundary,
+    Prop,
+}
+
+impl Default for Category {
+    fn default() -> Category {
+        Category::Unknown
+    }
+}
+
+// Stored on every body and fixture, links them back to the game entity
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EntityData {
+    pub entity: EntityId,
+    pub category: Category,
+}
+
+pub struct GameUserData;
+
+impl UserDataTypes for GameUserData {
+    type BodyData = EntityData;
+    type JointData = JointId;
+    type FixtureData = EntityData;
+}
+
+pub type BodyHandle = TypedHandle<b2::Body>;
+
+bitflags! {
+    // Which layers a body is on (CollisionFilter::layer) and which it collides with (mask)
+    pub struct CollisionLayer: u16 {
+        const LAYER_DEFAULT = 0x0001;
+        const LAYER_PLAYER = 0x0002;
+        const LAYER_WALL = 0x0004;
+        const LAYER_PICKUP = 0x0008;
+        const LAYER_PROJECTILE = 0x0010;
+        const LAYER_SENSOR = 0x0020;
+        const LAYER_TEAM_1 = 0x0100;
+        const LAYER_TEAM_2 = 0x0200;
+        const LAYER_TEAM_3 = 0x0400;
+        const LAYER_TEAM_4 = 0x0800;
+        const LAYER_ALL = 0xFFFF;
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct CollisionFilter {
+    pub layer: CollisionLayer,
+    pub mask: CollisionLayer,
+    // Bodies sharing a negative group never collide (e.g. a shooter and its projectiles),
+    // sharing a positive group always collide. 0 uses the layers only.
+    pub group: i16,
+}
+
+impl CollisionFilter {
+    pub fn new(layer: CollisionLayer, mask: CollisionLayer) -> CollisionFilter {
+        CollisionFilter {
+            layer: layer,
+            mask: mask,
+            group: 0,
+        }
+    }
+
+    // Box2D's default: on the default layer, colliding with everything
+    pub fn everything() -> CollisionFilter {
+        CollisionFilter::new(LAYER_DEFAULT, LAYER_ALL)
+    }
+
+    // Collides with nothing, for ghosts
+    pub fn nothing(layer: CollisionLayer) -> CollisionFilter {
+        CollisionFilter::new(layer, CollisionLayer::empty())
+    }
+
+    pub fn with_group(mut self, group: i16) -> CollisionFilter {
+        self.group = group;
+        return self;
+    }
+
+    fn to_b2(&self) -> b2::Filter {
+        b2::Filter {
+            category_bits: self.layer.bits(),
+            mask_bits: self.mask.bits(),
+            group_index: self.group,
+        }
+    }
+}
+
+impl Default for CollisionFilter {
+    fn default() -> CollisionFilter {
+        CollisionFilter::everything()
+    }
+}
+
+const SIZE_FACTOR: f32 = 10.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CollisionEvent {
+    // The entities started touching (or overlapping, for sensors)
+    Began { a: EntityId, b: EntityId },
+    // The entities stopped touching, also sent when one of them is destroyed
+    Ended { a: EntityId, b: EntityId },
+    // Strongest normal impulse between the entities during a step, in physics units
+    Impact { a: EntityId, b: EntityId, impulse: f32 },
+}
+
+impl CollisionEvent {
+    pub fn entities(&self) -> (EntityId, EntityId) {
+        return match *self {
+            CollisionEvent::Began { a, b } => (a, b),
+            CollisionEvent::Ended { a, b } => (a, b),
+            CollisionEvent::Impact { a, b, .. } => (a, b),
+        };
+    }
+
+    // The other entity when the event involves `entity`
+    pub fn other(&self, entity: EntityId) -> Option<EntityId> {
+        let (a, b) = self.entities();
+        if a == entity {
+            return Some(b);
+        }
+        if b == entity {
+            return Some(a);
+        }
+        return None;
+    }
+}
+
+// Closest fixture hit by a ray, point and normal in world coordinates
+#[derive(Debug, Clone, Copy)]
+pub struct RayHit {
+    pub entity: EntityId,
+    pub category: Category,
+    pub point: WorldPoint,
+    pub normal: WorldPoint,
+    // How far along the ray the hit is, 0 at `from` and 1 at `to`
+    pub fraction: f32,
+}
+
+pub struct PhysicsSystem {
+    world: b2::World<GameUserData>,
+    time_step: f32,
+    // Body transforms before the last step, for render interpolation
+    previous_transforms: Vec<(EntityId, b2::Transform)>,
+    next_entity: EntityId,
+    bodies: HashMap<EntityId, BodyHandle>,
+    // Filled by the contact listener, translated to collision events after each step
+    raw_contacts: Rc<RefCell<Vec<RawContact>>>,
+    // Touching fixture pairs per entity pair, bodies can have many fixtures
+    touching: HashMap<(EntityId, EntityId), u32>,
+    pending_collisions: Vec<CollisionEvent>,
+    collisions: Vec<CollisionEvent>,
+    next_joint: JointId,
+    joints: HashMap<JointId, JointRecord>,
+    // Joints broken during the last step
+    broken_joints: Vec<JointId>,
+    // Pairs touching when a snapshot was restored, their contacts are found again during the next step
+    restored_touching: Option<HashSet<(EntityId, EntityId)>>,
+}
+
+// Identity matrix
+const IDENTITY: [[f32; 4]; 4] =
+    [[1.0, 0.0, 0.0, 0.0], [0.0, 1.0, 0.0, 0.0], [0.0, 0.0, 1.0, 0.0], [0.0, 0.0, 0.0, 1.0]];
+
+impl PhysicsSystem {
+    pub fn new(time_step: f32) -> PhysicsSystem {
+        let gravity = B2Point { x: 0., y: 0.0 };
+        let mut world = b2::World::<GameUserData>::new(&gravity);
+
+        let raw_contacts = Rc::new(RefCell::new(vec![]));
+        world.set_contact_listener(Box::new(ContactRecorder { contacts: raw_contacts.clone() }));
+
+        PhysicsSystem {
+            world: world,
+            time_step: time_step,
+            previous_transforms: vec![],
+            next_entity: 0,
+            bodies: HashMap::new(),
+            raw_contacts: raw_contacts,
+            touching: HashMap::new(),
+            pending_collisions: vec![],
+            collisions: vec![],
+            next_joint: 0,
+            joints: HashMap::new(),
+            broken_joints: vec![],
+            restored_touching: None,
+        }
+    }
+
+    pub fn step(&mut self) -> () {
+        self.previous_transforms = self.world.bodies()
+            .map(|(_, body)| {
+                let body = body.borrow();
+                (body.user_data().entity, body.transform().clone())
+            })
+            .collect();
+
+        self.world.step(self.time_step, 6, 2);
+
+        self.collect_collisions();
+
+        // Pairs that were touching at the restore but aren't anymore
+        if let Some(restored) = self.restored_touching.take() {
+            for (a, b) in restored {
+                self.pending_collisions.push(CollisionEvent::Ended { a: a, b: b });
+            }
+        }
+
+        self.collisions = mem::replace(&mut self.pending_collisions, vec![]);
+
+        self.break_joints();
+    }
+
+    fn break_joints(&mut self) {
+        let inv_dt = 1.0 / self.time_step;
+
+        let mut broken = vec![];
+        for (&id, record) in self.joints.iter() {
+            let joint = self.world.joint(record.handle);
+            let force = joint.reaction_force(inv_dt).norm();
+            let torque = joint.reaction_torque(inv_dt).abs();
+
+            let force_exceeded = record.break_force.map_or(false, |limit| force > limit);
+            let torque_exceeded = record.break_torque.map_or(false, |limit| torque > limit);
+            if force_exceeded || torque_exceeded {
+                broken.push(id);
+            }
+        }
+
+        // Gears coupling a broken joint go with it and are reported too
+        self.broken_joints = vec![];
+        for id in broken {
+            let removed = self.remove_joint(id);
+            self.broken_joints.extend(removed);
+        }
+    }
+
+    // Destroy a joint and the gears coupling it, returns the ids of what was destroyed
+    fn remove_joint(&mut self, id: JointId) -> Vec<JointId> {
+        let mut removed = vec![];
+
+        let gears: Vec<JointId> = self.joints.iter()
+            .filter(|&(_, record)| record.coupled.map_or(false, |(a, b)| a == id || b == id))
+            .map(|(&gear, _)| gear)
+            .collect();
+        for gear in gears {
+            removed.extend(self.remove_joint(gear));
+        }
+
+        if let Some(record) = self.joints.remove(&id) {
+            self.world.destroy_joint(record.handle);
+            removed.push(id);
+        }
+        return removed;
+    }
+
+    // Seconds simulated by one step
+    pub fn time_step(&self) -> f32 {
+        return self.time_step;
+    }
+
+    // Joints that broke during the last step, they are already destroyed
+    pub fn broken_joints(&self) -> &[JointId] {
+        return &self.broken_joints;
+    }
+
+    // Anchors in the spec are world coordinates, so create joints after positioning the bodies
+    pub fn create_joint(&mut self, a: &PhysicsComponent, b: &PhysicsComponent, spec: &JointSpec) -> JointComponent {
+        let id = self.next_joint;
+        self.next_joint = self.next_joint + 1;
+
+        let coupled = match spec.kind {
+            JointKind::Gear { joint_a, joint_b, .. } => Some((joint_a, joint_b)),
+            _ => None,
+        };
+        let gear_joints = coupled.map(|(joint_a, joint_b)| {
+            match (self.joints.get(&joint_a), self.joints.get(&joint_b)) {
+                (Some(record_a), Some(record_b)) => (record_a.handle, record_b.handle),
+                _ => panic!("Gear joint {:?} couples a joint that doesn't exist", id),
+            }
+        });
+
+        let (body_a, body_b) = (self.body_handle(a), self.body_handle(b));
+        let handle = create_b2_joint(&mut self.world, body_a, body_b, spec, gear_joints, id);
+
+        // Not necessarily a and b, see JointRecord
+        let joint = self.world.joint(handle);
+        self.joints.insert(id, JointRecord {
+            handle: handle,
+            body_a: joint.body_a(),
+            body_b: joint.body_b(),
+            coupled: coupled,
+            break_force: spec.break_force,
+            break_torque: spec.break_torque,
+        });
+
+        return JointComponent::new(id);
+    }
+
+    // False once the joint was destroyed, broke, or one of its bodies was destroyed
+    pub fn contains_joint(&self, joint: &JointComponent) -> bool {
+        return self.joints.contains_key(&joint.id());
+    }
+
+    // Force the joint applied during the last step, in physics units
+    pub fn joint_reaction_force(&self, joint: &JointComponent) -> Option<f32> {
+        let inv_dt = 1.0 / self.time_step;
+        return self.joints.get(&joint.id())
+            .map(|record| self.world.joint(record.handle).reaction_force(inv_dt).norm());
+    }
+
+    pub fn joint_reaction_torque(&self, joint: &JointComponent) -> Option<f32> {
+        let inv_dt = 1.0 / self.time_step;
+        return self.joints.get(&joint.id())
+            .map(|record| self.world.joint(record.handle).reaction_torque(inv_dt));
+    }
+
+    // For revolute, prismatic and wheel joints created with a motor
+    pub fn set_joint_motor_speed(&mut self, joint: &JointComponent, speed: f32) {
+        if let Some(record) = self.joints.get(&joint.id()) {
+            let mut b2_joint = self.world.joint_mut(record.handle);
+            match **b2_joint {
+                b2::UnknownJoint::Revolute(ref mut revolute) => revolute.set_motor_speed(speed),
+                b2::UnknownJoint::Prismatic(ref mut prismatic) => prismatic.set_motor_speed(speed * SIZE_FACTOR),
+                b2::UnknownJoint::Wheel(ref mut wheel) => wheel.set_motor_speed(speed),
+                _ => warn!("Joint {:?} has no motor", joint.id()),
+            }
+        }
+    }
+
+    pub fn set_mouse_target(&mut self, joint: &JointComponent, target: WorldPoint) {
+        if let Some(record) = self.joints.get(&joint.id()) {
+            let mut b2_joint = self.world.joint_mut(record.handle);
+            if let b2::UnknownJoint::Mouse(ref mut mouse) = **b2_joint {
+                mouse.set_target(&world_to_physics(&target));
+            }
+        }
+    }
+
+    // Gears coupling the joint are destroyed with it
+    pub fn destroy_joint(&mut self, joint: &JointComponent) {
+        self.remove_joint(joint.id());
+    }
+
+    // Collisions that happened during the last step (and body destructions since the one before)
+    pub fn collisions(&self) -> &[CollisionEvent] {
+        return &self.collisions;
+    }
+
+    fn collect_collisions(&mut self) {
+        let raw_contacts = mem::replace(&mut *self.raw_contacts.borrow_mut(), vec![]);
+
+        for raw in raw_contacts {
+            let (a, b) = (raw.entity_a, raw.entity_b);
+            let pair = if a < b { (a, b) } else { (b, a) };
+
+            match raw.kind {
+                RawContactKind::Began => {
+                    let count = self.touching.entry(pair).or_insert(0);
+                    *count = *count + 1;
+
+                    // Already reported before the snapshot was restored
+                    let restored = match self.restored_touching {
+                        Some(ref mut restored) => restored.remove(&pair),
+                        None => false,
+                    };
+
+                    if *count == 1 && !restored {
+                        self.pending_collisions.push(CollisionEvent::Began { a: a, b: b });
+                    }
+                }
+                RawContactKind::Ended => {
+                    let ended = match self.touching.get_mut(&pair) {
+                        Some(count) => {
+                            *count = *count - 1;
+                            *count == 0
+                        }
+                        None => false,
+                    };
+                    if ended {
+                        self.touching.remove(&pair);
+                        self.pending_collisions.push(CollisionEvent::Ended { a: a, b: b });
+                    }
+                }
+                RawContactKind::Impact(impulse) => {
+                    // One impact per entity pair, multiple fixtures can be solved against each other
+                    let mut merged = false;
+                    for event in self.pending_collisions.iter_mut() {
+                        if let CollisionEvent::Impact { a: ea, b: eb, impulse: ref mut existing } = *event {
+                            if (ea, eb) == (a, b) || (ea, eb) == (b, a) {
+                                *existing = existing.max(impulse);
+                                merged = true;
+                            }
+                        }
+                    }
+                    if !merged {
+                        self.pending_collisions.push(CollisionEvent::Impact { a: a, b: b, impulse: impulse });
+                    }
+                }
+            }
+        }
+    }
+
+    // Create a body tagged with a fresh entity id, fixtures are added with create_fixture
+    fn create_entity_body(&mut self, body_def: &b2::BodyDef, category: Category) -> (BodyHandle, EntityData) {
+        let data = EntityData {
+            entity: self.next_entity,
+            category: category,
+        };
+        self.next_entity = self.next_entity + 1;
+
+        let body_handle = self.world.create_body_with(body_def, data);
+        self.bodies.insert(data.entity, body_handle);
+
+        return (body_handle, data);
+    }
+
+    fn create_fixture(&mut self, body_handle: BodyHandle, shape: &b2::Shape, fixture_def: &mut b2::FixtureDef) {
+        let mut body = self.world.body_mut(body_handle);
+        let data = *body.user_data();
+        body.create_fixture_with(shape, fixture_def, data);
+    }
+
+    fn body_handle(&self, physics_object: &PhysicsComponent) -> BodyHandle {
+        return *self.bodies.get(&physics_object.entity).expect("Physics component of a destroyed body");
+    }
+
+    // Entity owning a body, None if the body isn't (or no longer) part of this system
+    pub fn entity_of(&self, body_handle: BodyHandle) -> Option<EntityId> {
+        if !self.bodies.values().any(|&handle| handle == body_handle) {
+            return None;
+        }
+        return Some(self.world.body(body_handle).user_data().entity);
+    }
+
+    pub fn category_of(&self, entity: EntityId) -> Option<Category> {
+        return self.bodies.get(&entity).map(|&handle| self.world.body(handle).user_data().category);
+    }
+
+    pub fn contains(&self, entity: EntityId) -> bool {
+        return self.bodies.contains_key(&entity);
+    }
+
+    // Extra check run after the layer filtering, for rules layers can't express.
+    // Stays installed until clear_contact_filter is called.
+    pub fn set_contact_filter<F>(&mut self, hook: F)
+        where F: FnMut(&EntityData, &EntityData) -> bool + 'static
+    {
+        self.world.set_contact_filter(Box::new(LayerContactFilter { hook: Some(Box::new(hook)) }));
+    }
+
+    // Back to filtering on layers only
+    pub fn clear_contact_filter(&mut self) {
+        self.world.set_contact_filter(Box::new(LayerContactFilter { hook: None }));
+    }
+
+    // Change what an existing body collides with, e.g. to turn a player into a ghost
+    pub fn set_collision_filter(&mut self, physics_object: &PhysicsComponent, filter: CollisionFilter) {
+        let b2_filter = filter.to_b2();
+        let body = self.world.body(self.body_handle(physics_object));
+        for (_, fixture) in body.fixtures() {
+            fixture.borrow_mut().set_filter_data(&b2_filter);
+        }
+    }
+
+    pub fn create_boundary_sensor(&mut self, vertices: &Vec<WorldPoint>, filter: CollisionFilter) -> PhysicsComponent {
+        let mut body_def = b2::BodyDef::new();
+
+        body_def.body_type = b2::BodyType::Static;
+
+
+        let (body_handle, data) = self.create_entity_body(&body_def, Category::Boundary);
+
+        let physics_vertices: Vec<B2Point> = vertices.iter().map(|v| world_to_physics(v)).collect();
+
+        let chain_boundary = ChainShape::new_loop(&physics_vertices);
+
+        let mut fixture_def = b2::FixtureDef::new();
+        fixture_def.density = 0.0;
+        fixture_def.friction = 0.0;
+        fixture_def.is_sensor = true;
+        fixture_def.filter = filter.to_b2();
+
+        self.create_fixture(body_handle, &chain_boundary, &mut fixture_def);
+
+        return PhysicsComponent {
+            transform: IDENTITY,
+            entity: data.entity,
+        };
+    }
+
+    pub fn create_body(&mut self, spec: &BodySpec, category: Category, filter: CollisionFilter) -> PhysicsComponent {
+        let (body_handle, data) = self.create_entity_body(&body_def_from_spec(spec), category);
+
+        let mut fixture_def = fixture_def_from_spec(spec, filter);
+        for shape in spec.shapes.iter() {
+            self.create_spec_fixture(body_handle, shape, &mut fixture_def);
+        }
+
+        return PhysicsComponent {
+            transform: IDENTITY,
+            entity: data.entity,
+        };
+    }
+
+    // Triangles of the model become the body's fixtures, in addition to the shapes of the spec
+    pub fn create_body_stl(&mut self, stl: &'static [u8], spec: &BodySpec, category: Category, filter: CollisionFilter) -> PhysicsComponent {
+        use std::io::Cursor;
+
+        let mut model_reader = Cursor::new(stl.iter());
+
+        let stl_file = stl::read_stl(&mut model_reader).expect("Failed to load model");
+
+
+        let (body_handle, data) = self.create_entity_body(&body_def_from_spec(spec), category);
+
+
+        let mut fixture_def = fixture_def_from_spec(spec, filter);
+
+        for shape in spec.shapes.iter() {
+            self.create_spec_fixture(body_handle, shape, &mut fixture_def);
+        }
+
+        for t in stl_file.triangles.iter() {
+            let points = [b2::Vec2 {
+                x: t.v1[0] * SIZE_FACTOR,
+                y: t.v1[1] * SIZE_FACTOR,
+            },
+                b2::Vec2 {
+                    x: t.v2[0] * SIZE_FACTOR,
+                    y: t.v2[1] * SIZE_FACTOR,
+                },
+                b2::Vec2 {
+                    x: t.v3[0] * SIZE_FACTOR,
+                    y: t.v3[1] * SIZE_FACTOR,
+                }];
+            let body_box = b2::PolygonShape::new_with(&points);
+            self.create_fixture(body_handle, &body_box, &mut fixture_def);
+        }
+
+        return PhysicsComponent {
+            transform: IDENTITY,
+            entity: data.entity,
+        };
+    }
+
+    fn create_spec_fixture(&mut self, body_handle: BodyHandle, shape: &ShapeSpec, fixture_def: &mut b2::FixtureDef) {
+        match *shape {
+            ShapeSpec::Polygon { ref vertices } => {
+                let physics_vertices: Vec<B2Point> = vertices.iter()
+                    .map(|v| world_to_physics(&[v[0], v[1], 0.0]))
+                    .collect();
+                let polygon = b2::PolygonShape::new_with(&physics_vertices);
+                self.create_fixture(body_handle, &polygon, fixture_def);
+            }
+            ShapeSpec::Box { width, height, center, angle } => {
+                let center = world_to_physics(&[center[0], center[1], 0.0]);
+                let polygon = b2::PolygonShape::new_oriented_box(width * SIZE_FACTOR / 2.0,
+                                                                 height * SIZE_FACTOR / 2.0,
+                                                                 &center,
+                                                                 angle);
+                self.create_fixture(body_handle, &polygon, fixture_def);
+            }
+            ShapeSpec::Circle { radius, center } => {
+                let center = world_to_physics(&[center[0], center[1], 0.0]);
+                let circle = CircleShape::new_with(center, radius * SIZE_FACTOR);
+                self.create_fixture(body_handle, &circle, fixture_def);
+            }
+            ShapeSpec::Edge { from, to } => {
+                let edge = EdgeShape::new_with(&world_to_physics(&[from[0], from[1], 0.0]),
+                                               &world_to_physics(&[to[0], to[1], 0.0]));
+                self.create_fixture(body_handle, &edge, fixture_def);
+            }
+            ShapeSpec::Chain { ref vertices, looped } => {
+                let physics_vertices: Vec<B2Point> = vertices.iter()
+                    .map(|v| world_to_physics(&[v[0], v[1], 0.0]))
+                    .collect();
+                let chain = if looped {
+                    ChainShape::new_loop(&physics_vertices)
+                } else {
+                    ChainShape::new_chain(&physics_vertices)
+                };
+                self.create_fixture(body_handle, &chain, fixture_def);
+            }
+        }
+    }
+
+    // Body with a single circle, e.g. balls and round arenas
+    pub fn create_circle(&mut self, radius: f32, spec: &BodySpec, category: Category, filter: CollisionFilter) -> PhysicsComponent {
+        let spec = spec.clone().with_shape(ShapeSpec::circle(radius));
+        return self.create_body(&spec, category, filter);
+    }
+
+    pub fn create_edge(&mut self, from: &WorldPoint, to: &WorldPoint, spec: &BodySpec, category: Category, filter: CollisionFilter) -> PhysicsComponent {
+        let spec = spec.clone().with_shape(ShapeSpec::edge(from, to));
+        return self.create_body(&spec, category, filter);
+    }
+
+    // Open (terrain, tracks) or looped chain of edges, edges of a chain don't snag bodies sliding along them
+    pub fn create_chain(&mut self, vertices: &Vec<WorldPoint>, looped: bool, spec: &BodySpec, category: Category, filter: CollisionFilter) -> PhysicsComponent {
+        let spec = spec.clone().with_shape(ShapeSpec::chain(vertices, looped));
+        return self.create_body(&spec, category, filter);
+    }
+
+    // First non-sensor fixture between the points that collides with the filter,
+    // e.g. CollisionFilter::new(LAYER_PROJECTILE, LAYER_WALL) for line of sight through players
+    pub fn raycast(&self, from: WorldPoint, to: WorldPoint, filter: CollisionFilter) -> Option<RayHit> {
+        let ray_filter = filter.to_b2();
+        let mut closest: Option<RayHit> = None;
+
+        {
+            let world = &self.world;
+            let mut callback = |body_handle: BodyHandle, fixture_handle: b2::FixtureHandle, point: &B2Point, normal: &B2Point, fraction: f32| -> f32 {
+                let body = world.body(body_handle);
+                let fixture = body.fixture(fixture_handle);
+
+                if fixture.is_sensor() || !filters_collide(&ray_filter, fixture.filter_data()) {
+                    // Ignore the fixture and continue
+                    return -1.0;
+                }
+
+                let data = body.user_data();
+                closest = Some(RayHit {
+                    entity: data.entity,
+                    category: data.category,
+                    point: [point.x / SIZE_FACTOR, point.y / SIZE_FACTOR, 0.0],
+                    normal: [normal.x, normal.y, 0.0],
+                    fraction: fraction,
+                });
+
+                // Only look for closer hits from now on
+                return fraction;
+            };
+
+            self.world.ray_cast(&mut callback, &world_to_physics(&from), &world_to_physics(&to));
+        }
+
+        return closest;
+    }
+
+    // Entities with a fixture that collides with the filter and whose bounding box overlaps the rect
+    pub fn bodies_in_rect(&self, min: WorldPoint, max: WorldPoint, filter: CollisionFilter) -> Vec<EntityId> {
+        let query_filter = filter.to_b2();
+        let mut entities: Vec<EntityId> = vec![];
+
+        {
+            let world = &self.world;
+            let mut callback = |body_handle: BodyHandle, fixture_handle: b2::FixtureHandle| -> bool {
+                let body = world.body(body_handle);
+                let fixture = body.fixture(fixture_handle);
+
+                let entity = body.user_data().entity;
+                if filters_collide(&query_filter, fixture.filter_data()) && !entities.contains(&entity) {
+                    entities.push(entity);
+                }
+
+                // Keep going
+                return true;
+            };
+
+            let aabb = b2::AABB {
+                lower: world_to_physics(&[min[0].min(max[0]), min[1].min(max[1]), 0.0]),
+                upper: world_to_physics(&[min[0].max(max[0]), min[1].max(max[1]), 0.0]),
+            };
+            self.world.query_aabb(&mut callback, &aabb);
+        }
+
+        return entities;
+    }
+
+    // Capture bodies, fixtures, joints and the entities they belong to
+    pub fn snapshot(&self) -> PhysicsSnapshot {
+        let bodies = self.world.bodies()
+            .map(|(_, body)| {
+                let body = body.borrow();
+                let fixtures = body.fixtures()
+                    .map(|(_, fixture)| {
+                        let fixture = fixture.borrow();
+                        (FixtureSnapshot::take(&fixture), *fixture.user_data())
+                    })
+                    .collect();
+
+                let (id, body_snapshot) = BodySnapshot::take(&body);
+                BodyEntry {
+                    id: id,
+                    body: body_snapshot,
+                    data: *body.user_data(),
+                    fixtures: fixtures,
+                }
+            })
+            .collect();
+
+        // In creation order, gear joints refer to earlier joints
+        let mut joint_ids: Vec<&JointId> = self.joints.keys().collect();
+        joint_ids.sort();
+
+        let joints = joint_ids.into_iter()
+            .map(|id| {
+                let record = &self.joints[id];
+                let (b2_id, joint_snapshot) = JointSnapshot::take(&self.world.joint(record.handle));
+                JointEntry {
+                    id: *id,
+                    b2_id: b2_id,
+                    joint: joint_snapshot,
+                    coupled: record.coupled,
+                    break_force: record.break_force,
+                    break_torque: record.break_torque,
+                }
+            })
+            .collect();
+
+        return PhysicsSnapshot {
+            gravity: self.world.gravity(),
+            bodies: bodies,
+            joints: joints,
+            next_entity: self.next_entity,
+            next_joint: self.next_joint,
+            touching: self.touching.keys().cloned().collect(),
+        };
+    }
+
+    // Replace every body and joint with the ones in the snapshot. Components of entities in the
+    // snapshot keep working, bodies created since are gone. Contact caches aren't part of the
+    // snapshot, so the simulation continues closely but not bit for bit like the original.
+    pub fn restore(&mut self, snapshot: &PhysicsSnapshot) {
+        let handles: Vec<BodyHandle> = self.world.bodies().map(|(handle, _)| handle).collect();
+        for handle in handles {
+            // Takes the joints with it
+            self.world.destroy_body(handle);
+        }
+
+        self.bodies.clear();
+        self.joints.clear();
+        self.touching.clear();
+        self.raw_contacts.borrow_mut().clear();
+        self.pending_collisions.clear();
+        self.collisions.clear();
+        self.broken_joints.clear();
+        self.previous_transforms.clear();
+
+        self.world.set_gravity(&snapshot.gravity);
+
+        let mut id_to_handle = IdToHandle::new();
+        for entry in snapshot.bodies.iter() {
+            let handle = entry.body.rebuild(&mut self.world, entry.data);
+            id_to_handle.insert_body(entry.id, handle);
+            self.bodies.insert(entry.data.entity, handle);
+
+            let mut body = self.world.body_mut(handle);
+            for &(ref fixture, data) in entry.fixtures.iter() {
+                fixture.rebuild(&mut body, data);
+            }
+            entry.body.may_restore_mass_data(&mut body);
+        }
+
+        for entry in snapshot.joints.iter() {
+            let handle = match entry.joint.rebuild(&mut self.world, entry.id, &mut id_to_handle) {
+                Ok(handle) => handle,
+                Err(gear) => gear.rebuild(&mut self.world, entry.id, &mut id_to_handle),
+            };
+            id_to_handle.insert_joint(entry.b2_id, handle);
+
+            let joint = self.world.joint(handle);
+            self.joints.insert(entry.id, JointRecord {
+                handle: handle,
+                body_a: joint.body_a(),
+                body_b: joint.body_b(),
+                coupled: entry.coupled,
+                break_force: entry.break_force,
+                break_torque: entry.break_torque,
+            });
+        }
+
+        self.next_entity = snapshot.next_entity;
+        self.next_joint = snapshot.next_joint;
+        self.restored_touching = Some(snapshot.touching.iter().cloned().collect());
+    }
+
+    pub fn destroy_body(&mut self, physics_object: &PhysicsComponent) {
+        let body_handle = self.body_handle(physics_object);
+
+        // Box2D would destroy the body's joints with it, but not the gears coupling them
+        let attached: Vec<JointId> = self.joints.iter()
+            .filter(|&(_, record)| record.body_a == body_handle || record.body_b == body_handle)
+            .map(|(&id, _)| id)
+            .collect();
+        for id in attached {
+            self.remove_joint(id);
+        }
+
+        self.world.destroy_body(body_handle);
+
+        // Destroying a body ends its contacts
+        self.collect_collisions();
+        self.bodies.remove(&physics_object.entity);
+    }
+
+    // Inactive bodies don't move or collide, their contacts end right away
+    pub fn set_active(&mut self, physics_object: &PhysicsComponent, active: bool) {
+        let handle = self.body_handle(physics_object);
+        self.world.body_mut(handle).set_active(active);
+        self.collect_collisions();
+    }
+
+    pub fn apply_force_to_center(&self, force: WorldPoint, physics_object: &PhysicsComponent) {
+        let force_vec = world_to_physics(&force);
+        self.world.body_mut(self.body_handle(physics_object)).apply_force_to_center(&force_vec, true);
+    }
+
+    // Position of the body's origin in world coordinates
+    pub fn position(&self, physics_object: &PhysicsComponent) -> WorldPoint {
+        let body = self.world.body(self.body_handle(physics_object));
+        let position = body.position();
+        return [position.x / SIZE_FACTOR, position.y / SIZE_FACTOR, 0.0];
+    }
+
+    // World units per second
+    pub fn velocity(&self, physics_object: &PhysicsComponent) -> WorldPoint {
+        let body = self.world.body(self.body_handle(physics_object));
+        let velocity = body.linear_velocity();
+        return [velocity.x / SIZE_FACTOR, velocity.y / SIZE_FACTOR, 0.0];
+    }
+
+    pub fn set_velocity(&self, physics_object: &PhysicsComponent, velocity: WorldPoint) {
+        let velocity_vec = world_to_physics(&velocity);
+        self.world.body_mut(self.body_handle(physics_object)).set_linear_velocity(&velocity_vec);
+    }
+
+    // World units per second squared. Zero unless a game changes it, games that do reset it in teardown.
+    pub fn set_gravity(&mut self, gravity: WorldPoint) {
+        self.world.set_gravity(&world_to_physics(&gravity));
+    }
+
+    pub fn gravity(&self) -> WorldPoint {
+        let gravity = self.world.gravity();
+        return [gravity.x / SIZE_FACTOR, gravity.y / SIZE_FACTOR, 0.0];
+    }
+
+    // Radians, counter clockwise
+    pub fn angle(&self, physics_object: &PhysicsComponent) -> f32 {
+        return self.world.body(self.body_handle(physics_object)).angle();
+    }
+
+    pub fn mass(&self, physics_object: &PhysicsComponent) -> f32 {
+        return self.world.body(self.body_handle(physics_object)).mass();
+    }
+
+    // Teleport a body, e.g. to its start position. Velocities are kept.
+    pub fn set_transform(&mut self, physics_object: &PhysicsComponent, position: WorldPoint, angle: f32) {
+        let position_vec = world_to_physics(&position);
+        self.world.body_mut(self.body_handle(physics_object)).set_transform(&position_vec, angle);
+    }
+
+    // Instant change of momentum, in the same units as apply_force_to_center
+    pub fn apply_impulse_to_center(&self, impulse: WorldPoint, physics_object: &PhysicsComponent) {
+        let impulse_vec = world_to_physics(&impulse);
+        let mut body = self.world.body_mut(self.body_handle(physics_object));
+        let center = *body.world_center();
+        body.apply_linear_impulse(&impulse_vec, &center, true);
+    }
+
+    // Radians per second, counter clockwise
+    pub fn set_angular_velocity(&self, physics_object: &PhysicsComponent, angular_velocity: f32) {
+        self.world.body_mut(self.body_handle(physics_object)).set_angular_velocity(angular_velocity);
+    }
+
+    pub fn get_transformation(&self, physics_object: &PhysicsComponent) -> [[f32; 4]; 4] {
+        // Update transformation matrix
+        let body = self.world.body(self.body_handle(physics_object));
+        let transform = body.transform();
+
+        return transform_matrix(transform.pos.x, transform.pos.y, transform.rot.cos, transform.rot.sin);
+    }
+
+    // Transformation blended between the previous and the current step, alpha in [0, 1]
+    pub fn get_interpolated_transformation(&self, physics_object: &PhysicsComponent, alpha: f32) -> [[f32; 4]; 4] {
+        let body = self.world.body(self.body_handle(physics_object));
+        let current = body.transform();
+
+        let previous = self.previous_transforms.iter()
+            .filter(|&&(entity, _)| entity == physics_object.entity)
+            .map(|&(_, ref transform)| transform)
+            .next();
+
+        return match previous {
+            Some(previous) => {
+                let x = previous.pos.x + (current.pos.x - previous.pos.x) * alpha;
+                let y = previous.pos.y + (current.pos.y - previous.pos.y) * alpha;
+
+                // Interpolate along the shortest arc
+                let previous_angle = previous.rot.sin.atan2(previous.rot.cos);
+                let mut delta = current.rot.sin.atan2(current.rot.cos) - previous_angle;
+                if delta > PI {
+                    delta = delta - 2.0 * PI;
+                } else if delta < -PI {
+                    delta = delta + 2.0 * PI;
+                }
+                let angle = previous_angle + delta * alpha;
+
+                transform_matrix(x, y, angle.cos(), angle.sin())
+            }
+            // Created since the last step
+            None => transform_matrix(current.pos.x, current.pos.y, current.rot.cos, current.rot.sin),
+        };
+    }
+
+    // Position and angle of every body in world coordinates, in creation order
+    pub fn body_positions(&self) -> Vec<(WorldPoint, f32)> {
+        return self.world.bodies().map(|(_, body)| {
+            let body = body.borrow();
+            let position = body.position();
+            ([position.x / SIZE_FACTOR, position.y / SIZE_FACTOR, 0.0], body.angle())
+        }).collect();
+    }
+}
+
+// Bodies are looked up by entity, so components stay valid when the world is restored from a snapshot
+pub struct PhysicsComponent {
+    transform: [[f32; 4]; 4],
+    entity: EntityId,
+}
+
+impl PhysicsComponent {
+    pub fn entity(&self) -> EntityId {
+        return self.entity;
+    }
+}
+
+enum RawContactKind {
+    Began,
+    Ended,
+    Impact(f32),
+}
+
+struct RawContact {
+    kind: RawContactKind,
+    entity_a: EntityId,
+    entity_b: EntityId,
+}
+
+// Installed as the world's contact listener, queues contacts for the PhysicsSystem
+struct ContactRecorder {
+    contacts: Rc<RefCell<Vec<RawContact>>>,
+}
+
+impl ContactRecorder {
+    fn record(&mut self, contact: &ContactAccess<GameUserData>, kind: RawContactKind) {
+        self.contacts.borrow_mut().push(RawContact {
+            kind: kind,
+            entity_a: contact.body_a.user_data().entity,
+            entity_b: contact.body_b.user_data().entity,
+        });
+    }
+}
+
+impl b2::ContactListener<GameUserData> for ContactRecorder {
+    fn begin_contact(&mut self, contact: ContactAccess<GameUserData>) {
+        self.record(&contact, RawContactKind::Began);
+    }
+
+    fn end_contact(&mut self, contact: ContactAccess<GameUserData>) {
+        self.record(&contact, RawContactKind::Ended);
+    }
+
+    fn post_solve(&mut self, contact: ContactAccess<GameUserData>, impulse: &b2::ContactImpulse) {
+        let count = impulse.count as usize;
+
+        let mut max_impulse = 0f32;
+        for i in 0..count {
+            max_impulse = max_impulse.max(impulse.normal_impulses[i]);
+        }
+
+        self.record(&contact, RawContactKind::Impact(max_impulse));
+    }
+}
+
+// Installed as the world's contact filter. Setting a filter replaces Box2D's default
+// filtering, so the layer check is repeated here before the optional hook.
+struct LayerContactFilter {
+    hook: Option<Box<FnMut(&EntityData, &EntityData) -> bool>>,
+}
+
+impl b2::ContactFilter<GameUserData> for LayerContactFilter {
+    fn should_collide(&mut self,
+                      _: BodyAccess<GameUserData>,
+                      fixture_a: FixtureAccess<GameUserData>,
+                      _: BodyAccess<GameUserData>,
+                      fixture_b: FixtureAccess<GameUserData>) -> bool {
+        if !filters_collide(fixture_a.filter_data(), fixture_b.filter_data()) {
+            return false;
+        }
+
+        return match self.hook {
+            Some(ref mut hook) => hook(fixture_a.user_data(), fixture_b.user_data()),
+            None => true,
+        };
+    }
+}
+
+// Box2D's own group and layer rules
+fn filters_collide(filter_a: &b2::Filter, filter_b: &b2::Filter) -> bool {
+    if filter_a.group_index == filter_b.group_index && filter_a.group_index != 0 {
+        return filter_a.group_index > 0;
+    }
+    return (filter_a.mask_bits & filter_b.category_bits) != 0 && (filter_a.category_bits & filter_b.mask_bits) != 0;
+}
+
+fn body_def_from_spec(spec: &BodySpec) -> b2::BodyDef {
+    let mut body_def = b2::BodyDef::new();
+
+    body_def.body_type = match spec.body_type {
+        BodyKind::Static => b2::BodyType::Static,
+        BodyKind::Kinematic => b2::BodyType::Kinematic,
+        BodyKind::Dynamic => b2::BodyType::Dynamic,
+    };
+    body_def.linear_damping = spec.linear_damping;
+    body_def.angular_damping = spec.angular_damping;
+    body_def.bullet = spec.bullet;
+    body_def.fixed_rotation = spec.fixed_rotation;
+
+    return body_def;
+}
+
+fn fixture_def_from_spec(spec: &BodySpec, filter: CollisionFilter) -> b2::FixtureDef {
+    let mut fixture_def = b2::FixtureDef::new();
+
+    fixture_def.density = spec.density;
+    fixture_def.friction = spec.friction;
+    fixture_def.restitution = spec.restitution;
+    fixture_def.is_sensor = spec.sensor;
+    fixture_def.filter = filter.to_b2();
+
+    return fixture_def;
+}
+
+// Physics position and rotation to a world transformation matrix
+fn transform_matrix(x: f32, y: f32, cos: f32, sin: f32) -> [[f32; 4]; 4] {
+    return [[cos, sin, 0.0, 0.0],
+        [-sin, cos, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+        [x / SIZE_FACTOR, y / SIZE_FACTOR, 0.0, 1.0]];
+}
+
+fn world_to_physics(world: &WorldPoint) -> B2Point {
+    return B2Point {
+        x: world[0] * SIZE_FACTOR,
+        y: world[1] * SIZE_FACTOR,
+    };
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f32 = 0.0001;
+    const GEAR_RATIO: f32 = 2.0;
+
+    fn dynamic(density: f32) -> BodySpec {
+        BodySpec {
+            body_type: BodyKind::Dynamic,
+            density: density,
+            ..BodySpec::default()
+        }
+    }
+
+    fn circle(physics: &mut PhysicsSystem, radius: f32, spec: &BodySpec, position: WorldPoint) -> PhysicsComponent {
+        let body = physics.create_circle(radius, spec, Category::Prop, CollisionFilter::default());
+        physics.set_transform(&body, position, 0.0);
+        return body;
+    }
+
+    fn assert_near(actual: WorldPoint, expected: WorldPoint) {
+        assert!((actual[0] - expected[0]).abs() < EPSILON && (actual[1] - expected[1]).abs() < EPSILON,
+                "{:?} != {:?}", actual, expected);
+    }
+
+    // Bodies on revolute, prismatic and pulley joints, a gear coupling the two revolute joints,
+    // and a ball resting on a floor
+    struct Scene {
+        bodies: Vec<PhysicsComponent>,
+        joints: Vec<JointComponent>,
+        wheel_a: usize,
+        wheel_b: usize,
+        floor: usize,
+        ball: usize,
+    }
+
+    fn scene(physics: &mut PhysicsSystem) -> Scene {
+        physics.set_gravity([0.0, -2.0, 0.0]);
+
+        // Entity ids no longer follow Box2D's body order once something was destroyed
+        let gone = circle(physics, 0.1, &dynamic(1.0), [0.0, 0.5, 0.0]);
+        physics.destroy_body(&gone);
+
+        let ground = circle(physics, 0.05, &BodySpec::default(), [0.0, 0.0, 0.0]);
+        let wheel_a = circle(physics, 0.1, &dynamic(1.0), [-0.5, 0.0, 0.0]);
+        let wheel_b = circle(physics, 0.1, &dynamic(1.0), [0.5, 0.0, 0.0]);
+        let slider = circle(physics, 0.05, &dynamic(1.0), [0.0, 0.3, 0.0]);
+        let light = circle(physics, 0.05, &dynamic(1.0), [-0.8, 0.3, 0.0]);
+        let heavy = circle(physics, 0.1, &dynamic(1.0), [0.8, 0.3, 0.0]);
+        let floor_spec = BodySpec::default().with_shape(ShapeSpec::Box {
+            width: 2.0,
+            height: 0.1,
+            center: [0.0, 0.0],
+            angle: 0.0,
+        });
+        let floor = physics.create_body(&floor_spec, Category::Wall, CollisionFilter::default());
+        physics.set_transform(&floor, [0.0, -0.6, 0.0], 0.0);
+        let ball = circle(physics, 0.05, &dynamic(1.0), [0.0, -0.51, 0.0]);
+
+        let hinge_a = physics.create_joint(&ground, &wheel_a, &JointSpec::new(JointKind::Revolute {
+            anchor: [-0.5, 0.0, 0.0],
+            limits: None,
+            motor: None,
+        }));
+        let hinge_b = physics.create_joint(&ground, &wheel_b, &JointSpec::new(JointKind::Revolute {
+            anchor: [0.5, 0.0, 0.0],
+            limits: None,
+            motor: None,
+        }));
+        let gear = physics.create_joint(&wheel_a, &wheel_b, &JointSpec::new(JointKind::Gear {
+            joint_a: hinge_a.id(),
+            joint_b: hinge_b.id(),
+            ratio: GEAR_RATIO,
+        }));
+        let rail = physics.create_joint(&ground, &slider, &JointSpec::new(JointKind::Prismatic {
+            anchor: [0.0, 0.3, 0.0],
+            axis: [1.0, 0.0, 0.0],
+            limits: Some((-0.4, 0.4)),
+            motor: Some(JointMotor { speed: 0.5, max_effort: 100.0 }),
+        }));
+        let pulley = physics.create_joint(&light, &heavy, &JointSpec::new(JointKind::Pulley {
+            ground_a: [-0.8, 0.9, 0.0],
+            ground_b: [0.8, 0.9, 0.0],
+            anchor_a: [-0.8, 0.3, 0.0],
+            anchor_b: [0.8, 0.3, 0.0],
+            ratio: 1.0,
+        }));
+
+        physics.set_angular_velocity(&wheel_a, 3.0);
+
+        Scene {
+            bodies: vec![ground, wheel_a, wheel_b, slider, light, heavy, floor, ball],
+            joints: vec![hinge_a, hinge_b, gear, rail, pulley],
+            wheel_a: 1,
+            wheel_b: 2,
+            floor: 6,
+            ball: 7,
+        }
+    }
+
+    fn gear_coordinate(physics: &PhysicsSystem, scene: &Scene) -> f32 {
+        let a = physics.angle(&scene.bodies[scene.wheel_a]);
+        let b = physics.angle(&scene.bodies[scene.wheel_b]);
+        return a + GEAR_RATIO * b;
+    }
+
+    #[test]
+    fn restore_puts_bodies_back() {
+        let mut physics = PhysicsSystem::new(1.0 / 60.0);
+        let scene = scene(&mut physics);
+        for _ in 0..10 {
+            physics.step();
+        }
+
+        let positions: Vec<WorldPoint> = scene.bodies.iter().map(|b| physics.position(b)).collect();
+        let velocities: Vec<WorldPoint> = scene.bodies.iter().map(|b| physics.velocity(b)).collect();
+        let angles: Vec<f32> = scene.bodies.iter().map(|b| physics.angle(b)).collect();
+        let snapshot = physics.snapshot();
+
+        for _ in 0..30 {
+            physics.step();
+        }
+        assert!(scene.bodies.iter().zip(positions.iter()).any(|(b, &p)| physics.position(b) != p));
+
+        physics.restore(&snapshot);
+
+        for (i, body) in scene.bodies.iter().enumerate() {
+            assert!(physics.contains(body.entity()));
+            assert_near(physics.position(body), positions[i]);
+            assert_near(physics.velocity(body), velocities[i]);
+            assert!((physics.angle(body) - angles[i]).abs() < EPSILON);
+        }
+    }
+
+    #[test]
+    fn restore_drops_later_bodies_and_brings_back_destroyed_ones() {
+        let mut physics = PhysicsSystem::new(1.0 / 60.0);
+        let scene = scene(&mut physics);
+        let ball_position = physics.position(&scene.bodies[scene.ball]);
+        let snapshot = physics.snapshot();
+
+        let extra = circle(&mut physics, 0.1, &dynamic(1.0), [0.0, 0.7, 0.0]);
+        physics.destroy_body(&scene.bodies[scene.ball]);
+        physics.restore(&snapshot);
+
+        assert!(!physics.contains(extra.entity()));
+        assert!(physics.contains(scene.bodies[scene.ball].entity()));
+        assert_near(physics.position(&scene.bodies[scene.ball]), ball_position);
+
+        // Entity ids continue from the snapshot
+        let again = circle(&mut physics, 0.1, &dynamic(1.0), [0.0, 0.7, 0.0]);
+        assert_eq!(again.entity(), extra.entity());
+    }
+
+    #[test]
+    fn restore_rebuilds_joints_with_their_ids() {
+        let mut physics = PhysicsSystem::new(1.0 / 60.0);
+        let scene = scene(&mut physics);
+        for _ in 0..10 {
+            physics.step();
+        }
+        let coupled = gear_coordinate(&physics, &scene);
+        let snapshot = physics.snapshot();
+
+        // Destroying a hinge takes the gear with it, restoring brings both back
+        physics.destroy_joint(&scene.joints[0]);
+        assert!(!physics.contains_joint(&scene.joints[2]));
+        let later = physics.create_joint(&scene.bodies[0], &scene.bodies[3], &JointSpec::new(JointKind::Weld {
+            anchor: [0.0, 0.3, 0.0],
+            frequency: 0.0,
+            damping_ratio: 0.0,
+        }));
+
+        physics.restore(&snapshot);
+
+        for joint in scene.joints.iter() {
+            assert!(physics.contains_joint(joint));
+        }
+        assert!(!physics.contains_joint(&later));
+
+        // The gear still couples the restored hinges
+        for _ in 0..30 {
+            physics.step();
+        }
+        assert!((gear_coordinate(&physics, &scene) - coupled).abs() < 0.01);
+
+        let next = physics.create_joint(&scene.bodies[0], &scene.bodies[3], &JointSpec::new(JointKind::Weld {
+            anchor: [0.0, 0.3, 0.0],
+            frequency: 0.0,
+            damping_ratio: 0.0,
+        }));
+        assert_eq!(next.id(), later.id());
+    }
+
+    #[test]
+    fn restore_does_not_report_touching_pairs_again() {
+        let mut physics = PhysicsSystem::new(1.0 / 60.0);
+        let scene = scene(&mut physics);
+        let floor = scene.bodies[scene.floor].entity();
+        let ball = scene.bodies[scene.ball].entity();
+        let is_pair = |a: EntityId, b: EntityId| (a, b) == (floor, ball) || (a, b) == (ball, floor);
+
+        physics.step();
+        assert!(physics.collisions().iter().any(|c| match *c {
+            CollisionEvent::Began { a, b } => is_pair(a, b),
+            _ => false,
+        }));
+        let snapshot = physics.snapshot();
+
+        physics.step();
+        physics.restore(&snapshot);
+        physics.step();
+
+        // Still resting on the floor, neither a new contact nor an end
+        assert!(!physics.collisions().iter().any(|c| match *c {
+            CollisionEvent::Began { a, b } | CollisionEvent::Ended { a, b } => is_pair(a, b),
+            _ => false,
+        }));
+
+        // Moved away before the contact was found again, the restored contact ends
+        physics.restore(&snapshot);
+        physics.set_transform(&scene.bodies[scene.ball], [0.0, 0.5, 0.0], 0.0);
+        physics.step();
+        assert!(physics.collisions().iter().any(|c| match *c {
+            CollisionEvent::Ended { a, b } => is_pair(a, b),
+            _ => false,
+        }));
+    }
+}
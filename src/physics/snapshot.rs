@@ -0,0 +1,42 @@
+use super::wrapped2d::b2;
+use super::wrapped2d::serialize::{BodySnapshot, FixtureSnapshot, JointSnapshot};
+use super::wrapped2d::serialize::{BodyId, JointId as B2JointId};
+use super::{EntityId, EntityData, JointId};
+
+// Everything needed to put a PhysicsSystem back into an earlier state:
+// bodies with their fixtures, joints, and which entity each of them belongs to
+#[derive(Clone)]
+pub struct PhysicsSnapshot {
+    pub gravity: b2::Vec2,
+    pub bodies: Vec<BodyEntry>,
+    pub joints: Vec<JointEntry>,
+    pub next_entity: EntityId,
+    pub next_joint: JointId,
+    // Entity pairs that were touching, so restoring doesn't report their contacts again
+    pub touching: Vec<(EntityId, EntityId)>,
+}
+
+#[derive(Clone)]
+pub struct BodyEntry {
+    // Box2D's id for the body, joints refer to bodies by it
+    pub id: BodyId,
+    pub body: BodySnapshot,
+    pub data: EntityData,
+    pub fixtures: Vec<(FixtureSnapshot, EntityData)>,
+}
+
+#[derive(Clone)]
+pub struct JointEntry {
+    pub id: JointId,
+    pub b2_id: B2JointId,
+    pub joint: JointSnapshot,
+    pub coupled: Option<(JointId, JointId)>,
+    pub break_force: Option<f32>,
+    pub break_torque: Option<f32>,
+}
+
+impl PhysicsSnapshot {
+    pub fn contains(&self, entity: EntityId) -> bool {
+        return self.bodies.iter().any(|entry| entry.data.entity == entity);
+    }
+}
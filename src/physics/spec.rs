@@ -0,0 +1,179 @@
+extern crate toml;
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::prelude::*;
+
+use game::minigame::Point as WorldPoint;
+
+pub const BODIES_FILE: &str = "bodies.toml";
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BodyKind {
+    Static,
+    Kinematic,
+    Dynamic,
+}
+
+// Shapes are in world coordinates, relative to the body origin
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ShapeSpec {
+    // Convex, at most 8 vertices
+    Polygon { vertices: Vec<[f32; 2]> },
+    Box {
+        width: f32,
+        height: f32,
+        #[serde(default)]
+        center: [f32; 2],
+        #[serde(default)]
+        angle: f32,
+    },
+    Circle {
+        radius: f32,
+        #[serde(default)]
+        center: [f32; 2],
+    },
+    // Two sided line segment
+    Edge { from: [f32; 2], to: [f32; 2] },
+    // Connected edges for terrain and tracks, closed when looped
+    Chain {
+        vertices: Vec<[f32; 2]>,
+        #[serde(default)]
+        looped: bool,
+    },
+}
+
+impl ShapeSpec {
+    pub fn polygon(vertices: &Vec<WorldPoint>) -> ShapeSpec {
+        ShapeSpec::Polygon { vertices: vertices.iter().map(|v| [v[0], v[1]]).collect() }
+    }
+
+    pub fn circle(radius: f32) -> ShapeSpec {
+        ShapeSpec::Circle {
+            radius: radius,
+            center: [0.0, 0.0],
+        }
+    }
+
+    pub fn edge(from: &WorldPoint, to: &WorldPoint) -> ShapeSpec {
+        ShapeSpec::Edge {
+            from: [from[0], from[1]],
+            to: [to[0], to[1]],
+        }
+    }
+
+    pub fn chain(vertices: &Vec<WorldPoint>, looped: bool) -> ShapeSpec {
+        ShapeSpec::Chain {
+            vertices: vertices.iter().map(|v| [v[0], v[1]]).collect(),
+            looped: looped,
+        }
+    }
+
+    // What keeps Box2D from building the shape, if anything
+    pub fn problem(&self) -> Option<String> {
+        return match *self {
+            ShapeSpec::Polygon { ref vertices } if vertices.len() < 3 || vertices.len() > 8 =>
+                Some(format!("polygon with {} vertices, needs 3 to 8", vertices.len())),
+            ShapeSpec::Box { width, height, .. } if !(width > 0.0 && height > 0.0) =>
+                Some(format!("box of {} by {}", width, height)),
+            ShapeSpec::Circle { radius, .. } if !(radius > 0.0) =>
+                Some(format!("circle with radius {}", radius)),
+            ShapeSpec::Edge { from, to } if from == to =>
+                Some(format!("edge of length 0 at {:?}", from)),
+            ShapeSpec::Chain { ref vertices, looped } if vertices.len() < if looped { 3 } else { 2 } =>
+                Some(format!("chain with {} vertices", vertices.len())),
+            _ => None,
+        };
+    }
+}
+
+// Physics material and shapes of a body, loaded from bodies.toml so it can be tuned without recompiling
+#[derive(Debug, Clone, Deserialize)]
+pub struct BodySpec {
+    #[serde(default = "default_body_type")]
+    pub body_type: BodyKind,
+    #[serde(default)]
+    pub density: f32,
+    #[serde(default = "default_friction")]
+    pub friction: f32,
+    #[serde(default)]
+    pub restitution: f32,
+    #[serde(default)]
+    pub linear_damping: f32,
+    #[serde(default)]
+    pub angular_damping: f32,
+    // Continuous collision detection against other dynamic bodies, for fast movers
+    #[serde(default)]
+    pub bullet: bool,
+    #[serde(default)]
+    pub fixed_rotation: bool,
+    #[serde(default)]
+    pub sensor: bool,
+    #[serde(default)]
+    pub shapes: Vec<ShapeSpec>,
+}
+
+// Box2D's own defaults
+fn default_body_type() -> BodyKind {
+    BodyKind::Static
+}
+
+fn default_friction() -> f32 {
+    0.2
+}
+
+impl Default for BodySpec {
+    fn default() -> BodySpec {
+        BodySpec {
+            body_type: default_body_type(),
+            density: 0.0,
+            friction: default_friction(),
+            restitution: 0.0,
+            linear_damping: 0.0,
+            angular_damping: 0.0,
+            bullet: false,
+            fixed_rotation: false,
+            sensor: false,
+            shapes: vec![],
+        }
+    }
+}
+
+impl BodySpec {
+    pub fn with_shape(mut self, shape: ShapeSpec) -> BodySpec {
+        self.shapes.push(shape);
+        return self;
+    }
+}
+
+// Named body specs, e.g. [sumo_player] in bodies.toml
+pub struct BodySpecs {
+    specs: HashMap<String, BodySpec>,
+}
+
+impl BodySpecs {
+    pub fn load(path: &str) -> Result<BodySpecs, toml::de::Error> {
+        let mut input = String::new();
+
+        File::open(path.to_string()).and_then(|mut f| {
+            f.read_to_string(&mut input)
+        }).unwrap();
+
+        let specs = toml::from_str(&input)?;
+
+        return Ok(BodySpecs { specs: specs });
+    }
+
+    // Falls back to Box2D's defaults so a missing entry doesn't stop the game
+    pub fn get(&self, name: &str) -> BodySpec {
+        match self.specs.get(name) {
+            Some(spec) => spec.clone(),
+            None => {
+                warn!("No body spec named {:?}, using defaults", name);
+                BodySpec::default()
+            }
+        }
+    }
+}
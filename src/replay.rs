@@ -0,0 +1,625 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::process;
+
+use config::{Config, AvoidTheWallConfig};
+use draw::DrawSystem;
+use physics::PhysicsSystem;
+use input::{InputSystem, InputEvent, ControllerState, ID};
+use input::InputEvent::{InputAdded, InputRemoved};
+use game::minigame::Point as WorldPoint;
+use game::minigame::MiniGameSettings;
+use game::registry::MiniGameRegistry;
+use game::session::Session;
+
+const MAGIC: &'static [u8; 4] = b"ZSRP";
+const VERSION: u8 = 6;
+
+const EVENT_ADDED: u8 = 0;
+const EVENT_REMOVED: u8 = 1;
+
+// Everything needed to play a match again: the seed, the playlist and the input of every tick
+pub struct Replay {
+    pub seed: u64,
+    // Length of a tick in seconds, exactly as the recording used it
+    pub dt: f32,
+    pub playlist: Vec<String>,
+    // Minigame tuning from the recording's settings.toml
+    pub settings: MiniGameSettings,
+    pub ticks: Vec<ReplayTick>,
+    // Body positions and angles after the last tick, to verify playback against
+    pub final_bodies: Vec<(WorldPoint, f32)>,
+}
+
+#[derive(Default)]
+pub struct ReplayTick {
+    pub events: Vec<InputEvent>,
+    // GUIDs of the controllers added this tick, the roster matches reconnects by them
+    pub guids: Vec<(ID, String)>,
+    // Only the controllers whose state changed since the tick before
+    pub states: Vec<RecordedState>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RecordedState {
+    pub id: ID,
+    // Masks of input::button_bit()s
+    pub buttons: u32,
+    pub pressed: u32,
+    pub released: u32,
+    // Raw left trigger, right trigger, left x, left y, right x, right y
+    pub axes: [i16; 6],
+    // The same axes processed, exactly as games saw them. Bots write these directly, and
+    // recording them keeps replays independent of the response settings.
+    pub processed: [f32; 6],
+}
+
+impl RecordedState {
+    // Call after InputSystem::tick, so the edges of the tick are included
+    pub fn take(state: &ControllerState) -> RecordedState {
+        RecordedState {
+            id: state.inst_id,
+            buttons: state.buttons(),
+            pressed: state.pressed,
+            released: state.released,
+            axes: [state.axis_l_trigger,
+                   state.axis_r_trigger,
+                   state.axis_l_x,
+                   state.axis_l_y,
+                   state.axis_r_x,
+                   state.axis_r_y],
+            processed: [state.left_trigger,
+                        state.right_trigger,
+                        state.left_stick[0],
+                        state.left_stick[1],
+                        state.right_stick[0],
+                        state.right_stick[1]],
+        }
+    }
+
+    pub fn apply(&self, state: &mut ControllerState) {
+        state.set_buttons(self.buttons);
+        state.replay_edges(self.pressed, self.released);
+
+        state.axis_l_trigger = self.axes[0];
+        state.axis_r_trigger = self.axes[1];
+        state.axis_l_x = self.axes[2];
+        state.axis_l_y = self.axes[3];
+        state.axis_r_x = self.axes[4];
+        state.axis_r_y = self.axes[5];
+
+        state.left_trigger = self.processed[0];
+        state.right_trigger = self.processed[1];
+        state.left_stick = [self.processed[2], self.processed[3]];
+        state.right_stick = [self.processed[4], self.processed[5]];
+    }
+
+    // Edges only last one tick, so a state with edges is never the same as the last one
+    fn has_edges(&self) -> bool {
+        return self.pressed != 0 || self.released != 0;
+    }
+}
+
+impl Replay {
+    pub fn load(path: &str) -> io::Result<Replay> {
+        let mut bytes = vec![];
+        File::open(path)?.read_to_end(&mut bytes)?;
+
+        let mut reader = Reader { bytes: &bytes, pos: 0 };
+
+        if reader.take(4)? != &MAGIC[..] {
+            return Err(invalid_data("not a replay file"));
+        }
+        let version = reader.u8()?;
+        if version != VERSION {
+            return Err(invalid_data(&format!("unsupported replay version {}", version)));
+        }
+
+        let seed = reader.u64()?;
+        let dt = reader.f32()?;
+
+        let mut playlist = vec![];
+        for _ in 0..reader.u32()? {
+            playlist.push(reader.string()?);
+        }
+
+        let ramp = AvoidTheWallConfig {
+            start_speed: reader.f32()?,
+            speed_step: reader.f32()?,
+            max_speed: reader.f32()?,
+            start_interval: reader.f32()?,
+            interval_step: reader.f32()?,
+            min_interval: reader.f32()?,
+            start_gap: reader.f32()?,
+            gap_step: reader.f32()?,
+            min_gap: reader.f32()?,
+        };
+        let settings = MiniGameSettings { avoid_the_wall: ramp };
+
+        let mut ticks = vec![];
+        for _ in 0..reader.u32()? {
+            let mut tick = ReplayTick::default();
+
+            for _ in 0..reader.u8()? {
+                let kind = reader.u8()?;
+                let id = reader.i32()?;
+                tick.events.push(match kind {
+                    EVENT_ADDED => InputAdded(id),
+                    EVENT_REMOVED => InputRemoved(id),
+                    _ => return Err(invalid_data("unknown input event")),
+                });
+            }
+
+            for _ in 0..reader.u8()? {
+                let id = reader.i32()?;
+                tick.guids.push((id, reader.string()?));
+            }
+
+            for _ in 0..reader.u8()? {
+                let id = reader.i32()?;
+                let buttons = reader.u32()?;
+                let pressed = reader.u32()?;
+                let released = reader.u32()?;
+                let mut axes = [0i16; 6];
+                for axis in axes.iter_mut() {
+                    *axis = reader.u16()? as i16;
+                }
+                let mut processed = [0f32; 6];
+                for value in processed.iter_mut() {
+                    *value = reader.f32()?;
+                }
+                tick.states.push(RecordedState {
+                    id: id,
+                    buttons: buttons,
+                    pressed: pressed,
+                    released: released,
+                    axes: axes,
+                    processed: processed,
+                });
+            }
+
+            ticks.push(tick);
+        }
+
+        let mut final_bodies = vec![];
+        for _ in 0..reader.u32()? {
+            let x = reader.f32()?;
+            let y = reader.f32()?;
+            let angle = reader.f32()?;
+            final_bodies.push(([x, y, 0.0], angle));
+        }
+
+        return Ok(Replay {
+            seed: seed,
+            dt: dt,
+            playlist: playlist,
+            settings: settings,
+            ticks: ticks,
+            final_bodies: final_bodies,
+        });
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let mut out: Vec<u8> = vec![];
+
+        out.extend_from_slice(MAGIC);
+        out.push(VERSION);
+        write_u64(&mut out, self.seed);
+        write_u32(&mut out, self.dt.to_bits());
+
+        write_u32(&mut out, self.playlist.len() as u32);
+        for name in self.playlist.iter() {
+            write_string(&mut out, name);
+        }
+
+        let ramp = &self.settings.avoid_the_wall;
+        for &value in [ramp.start_speed, ramp.speed_step, ramp.max_speed,
+                       ramp.start_interval, ramp.interval_step, ramp.min_interval,
+                       ramp.start_gap, ramp.gap_step, ramp.min_gap].iter() {
+            write_u32(&mut out, value.to_bits());
+        }
+
+        write_u32(&mut out, self.ticks.len() as u32);
+        for tick in self.ticks.iter() {
+            out.push(tick.events.len() as u8);
+            for event in tick.events.iter() {
+                let (kind, id) = match *event {
+                    InputAdded(id) => (EVENT_ADDED, id),
+                    InputRemoved(id) => (EVENT_REMOVED, id),
+                };
+                out.push(kind);
+                write_u32(&mut out, id as u32);
+            }
+
+            out.push(tick.guids.len() as u8);
+            for &(id, ref guid) in tick.guids.iter() {
+                write_u32(&mut out, id as u32);
+                write_string(&mut out, guid);
+            }
+
+            out.push(tick.states.len() as u8);
+            for state in tick.states.iter() {
+                write_u32(&mut out, state.id as u32);
+                write_u32(&mut out, state.buttons);
+                write_u32(&mut out, state.pressed);
+                write_u32(&mut out, state.released);
+                for &axis in state.axes.iter() {
+                    write_u16(&mut out, axis as u16);
+                }
+                for &value in state.processed.iter() {
+                    write_u32(&mut out, value.to_bits());
+                }
+            }
+        }
+
+        write_u32(&mut out, self.final_bodies.len() as u32);
+        for &(position, angle) in self.final_bodies.iter() {
+            write_u32(&mut out, position[0].to_bits());
+            write_u32(&mut out, position[1].to_bits());
+            write_u32(&mut out, angle.to_bits());
+        }
+
+        return File::create(path)?.write_all(&out);
+    }
+}
+
+// Records the input of every tick, call record_tick between InputSystem::tick and the session step
+pub struct ReplayRecorder {
+    path: String,
+    replay: Replay,
+    last_states: HashMap<ID, RecordedState>,
+}
+
+impl ReplayRecorder {
+    pub fn new(path: String,
+               seed: u64,
+               dt: f32,
+               playlist: &Vec<String>,
+               settings: &MiniGameSettings,
+               input: &mut InputSystem) -> ReplayRecorder {
+        input.start_event_log();
+        info!("Recording replay to {:?}", path);
+
+        ReplayRecorder {
+            path: path,
+            replay: Replay {
+                seed: seed,
+                dt: dt,
+                playlist: playlist.clone(),
+                settings: settings.clone(),
+                ticks: vec![],
+                final_bodies: vec![],
+            },
+            last_states: HashMap::new(),
+        }
+    }
+
+    pub fn record_tick(&mut self, input: &mut InputSystem) {
+        let mut tick = ReplayTick::default();
+        tick.events = input.take_event_log();
+        for event in tick.events.iter() {
+            if let InputAdded(id) = *event {
+                if let Some(state) = input.get_controller_state(id) {
+                    tick.guids.push((id, state.guid.clone()));
+                }
+            }
+        }
+
+        for id in input.controller_ids() {
+            if let Some(state) = input.get_controller_state(id) {
+                let recorded = RecordedState::take(state);
+                if recorded.has_edges() || self.last_states.get(&id) != Some(&recorded) {
+                    self.last_states.insert(id, recorded);
+                    tick.states.push(recorded);
+                }
+            }
+        }
+
+        self.replay.ticks.push(tick);
+    }
+
+    // Write the replay, call before the session tears down the current minigame
+    pub fn finish(mut self, physics: &PhysicsSystem) {
+        self.replay.final_bodies = physics.body_positions();
+
+        match self.replay.save(&self.path) {
+            Ok(()) => info!("Saved replay of {} ticks to {:?}", self.replay.ticks.len(), self.path),
+            Err(err) => error!("Failed to save replay to {:?}: {}", self.path, err),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ReplayOptions {
+    pub path: String,
+    // Check that the bodies end up where they did in the recording
+    pub verify: bool,
+}
+
+impl ReplayOptions {
+    // Returns None unless --replay <file> was passed
+    pub fn from_args<I: Iterator<Item = String>>(args: I) -> Option<ReplayOptions> {
+        let mut path = None;
+        let mut verify = false;
+
+        let mut args = args.skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_ref() {
+                "--replay" => path = Some(args.next().expect("--replay needs a file")),
+                "--verify" => verify = true,
+                _ => {}
+            }
+        }
+
+        return path.map(|path| ReplayOptions {
+            path: path,
+            verify: verify,
+        });
+    }
+}
+
+// File passed with --record <file>, if any
+pub fn record_path_from_args<I: Iterator<Item = String>>(args: I) -> Option<String> {
+    let mut args = args.skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--record" {
+            return Some(args.next().expect("--record needs a file"));
+        }
+    }
+    return None;
+}
+
+// Play a recorded match without a window, one tick per recorded tick
+pub fn run(config: Config, options: ReplayOptions) {
+    let replay = match Replay::load(&options.path) {
+        Ok(replay) => replay,
+        Err(err) => panic!("Failed to load replay {:?}: {}", options.path, err),
+    };
+    info!("Playing replay {:?}: {} ticks, seed {}", options.path, replay.ticks.len(), replay.seed);
+
+    let mut draw_system = DrawSystem::new_headless();
+    let mut physics_system = PhysicsSystem::new(replay.dt);
+    let mut input_system = InputSystem::new_headless(config.input);
+
+    let mut session = Session::new(MiniGameRegistry::with_defaults(replay.settings.clone()), replay.playlist.clone(), replay.seed, &draw_system);
+
+    for tick in replay.ticks.iter() {
+        for event in tick.events.iter() {
+            match *event {
+                InputAdded(id) => {
+                    let guid = tick.guids.iter().filter(|g| g.0 == id).map(|g| g.1.clone()).next();
+                    input_system.add_replayed_controller(id, guid.unwrap_or(String::new()));
+                }
+                InputRemoved(id) => input_system.remove_virtual_controller(id),
+            }
+        }
+
+        for recorded in tick.states.iter() {
+            if let Some(state) = input_system.get_controller_state_mut(recorded.id) {
+                recorded.apply(state);
+            }
+        }
+        input_system.tick(replay.dt);
+
+        draw_system.pre_render();
+
+        session.step(&mut draw_system, &mut physics_system, &mut input_system);
+        session.render(&mut draw_system, &physics_system, 1.0);
+
+        draw_system.post_render();
+    }
+
+    println!("Replay finished after {} ticks", replay.ticks.len());
+    println!("Minigames finished: {}", session.finished_count());
+
+    let mismatches = if options.verify {
+        verify(&replay.final_bodies, &physics_system.body_positions())
+    } else {
+        0
+    };
+
+    session.finish_current(&mut physics_system);
+
+    if mismatches > 0 {
+        process::exit(1);
+    }
+}
+
+// Print differences between the recorded and the replayed bodies, returns how many differ
+fn verify(expected: &Vec<(WorldPoint, f32)>, actual: &Vec<(WorldPoint, f32)>) -> usize {
+    if expected.len() != actual.len() {
+        println!("Verification failed: recorded {} bodies, replay has {}", expected.len(), actual.len());
+        return expected.len().max(actual.len());
+    }
+
+    let mut mismatches = 0;
+    for (i, (&(e_pos, e_angle), &(a_pos, a_angle))) in expected.iter().zip(actual.iter()).enumerate() {
+        if e_pos[0] != a_pos[0] || e_pos[1] != a_pos[1] || e_angle != a_angle {
+            println!("  body {}: recorded ({}, {}) angle {}, replayed ({}, {}) angle {}",
+                     i, e_pos[0], e_pos[1], e_angle, a_pos[0], a_pos[1], a_angle);
+            mismatches = mismatches + 1;
+        }
+    }
+
+    if mismatches == 0 {
+        println!("Verification passed: {} bodies match", expected.len());
+    } else {
+        println!("Verification failed: {} of {} bodies differ", mismatches, expected.len());
+    }
+
+    return mismatches;
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, count: usize) -> io::Result<&'a [u8]> {
+        if self.pos + count > self.bytes.len() {
+            return Err(invalid_data("replay file is truncated"));
+        }
+        let bytes = self.bytes;
+        let slice = &bytes[self.pos..self.pos + count];
+        self.pos = self.pos + count;
+        return Ok(slice);
+    }
+
+    fn u8(&mut self) -> io::Result<u8> {
+        return Ok(self.take(1)?[0]);
+    }
+
+    fn u16(&mut self) -> io::Result<u16> {
+        let b = self.take(2)?;
+        return Ok(b[0] as u16 | (b[1] as u16) << 8);
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        let b = self.take(4)?;
+        return Ok(b[0] as u32 | (b[1] as u32) << 8 | (b[2] as u32) << 16 | (b[3] as u32) << 24);
+    }
+
+    fn u64(&mut self) -> io::Result<u64> {
+        let low = self.u32()? as u64;
+        let high = self.u32()? as u64;
+        return Ok(low | high << 32);
+    }
+
+    fn i32(&mut self) -> io::Result<i32> {
+        return Ok(self.u32()? as i32);
+    }
+
+    fn f32(&mut self) -> io::Result<f32> {
+        return Ok(f32::from_bits(self.u32()?));
+    }
+
+    fn string(&mut self) -> io::Result<String> {
+        let len = self.u32()? as usize;
+        let bytes = self.take(len)?;
+        return String::from_utf8(bytes.to_vec()).map_err(|_| invalid_data("invalid string"));
+    }
+}
+
+// Little endian
+fn write_u16(out: &mut Vec<u8>, value: u16) {
+    out.push(value as u8);
+    out.push((value >> 8) as u8);
+}
+
+fn write_u32(out: &mut Vec<u8>, value: u32) {
+    write_u16(out, value as u16);
+    write_u16(out, (value >> 16) as u16);
+}
+
+fn write_string(out: &mut Vec<u8>, value: &str) {
+    write_u32(out, value.len() as u32);
+    out.extend_from_slice(value.as_bytes());
+}
+
+fn write_u64(out: &mut Vec<u8>, value: u64) {
+    write_u32(out, value as u32);
+    write_u32(out, (value >> 32) as u32);
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    return io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    // One file per test, tests run in parallel
+    fn temp_path(name: &str) -> String {
+        let file = format!("zoo_spree_{}.replay", name);
+        return env::temp_dir().join(file).to_string_lossy().into_owned();
+    }
+
+    fn sample() -> Replay {
+        let mut settings = MiniGameSettings { avoid_the_wall: AvoidTheWallConfig::default() };
+        settings.avoid_the_wall.max_speed = 1.5;
+
+        let mut first = ReplayTick::default();
+        first.events = vec![InputAdded(0), InputAdded(-100)];
+        first.guids = vec![(0, "030000005e0400008e02000014010000".to_string())];
+        first.states = vec![RecordedState {
+            id: 0,
+            buttons: 0b101,
+            pressed: 0b100,
+            released: 0,
+            axes: [0, 32767, -32768, 1200, -5, 0],
+            processed: [0.0, 1.0, -1.0, 0.03, 0.0, 0.0],
+        }];
+
+        let mut second = ReplayTick::default();
+        second.events = vec![InputRemoved(-100)];
+
+        Replay {
+            seed: 0xDEAD_BEEF_0123_4567,
+            dt: 1.0 / 60.0,
+            playlist: vec!["sumo".to_string(), "snake".to_string()],
+            settings: settings,
+            ticks: vec![first, ReplayTick::default(), second],
+            final_bodies: vec![([0.25, -0.5, 0.0], 1.25), ([-0.75, 0.125, 0.0], -3.0)],
+        }
+    }
+
+    #[test]
+    fn save_then_load_round_trips() {
+        let path = temp_path("round_trip");
+        let replay = sample();
+        replay.save(&path).unwrap();
+        let loaded = Replay::load(&path);
+        fs::remove_file(&path).unwrap();
+        let loaded = loaded.unwrap();
+
+        assert_eq!(loaded.seed, replay.seed);
+        assert_eq!(loaded.dt, replay.dt);
+        assert_eq!(loaded.playlist, replay.playlist);
+        assert_eq!(loaded.settings.avoid_the_wall.max_speed, 1.5);
+        assert_eq!(loaded.settings.avoid_the_wall.min_gap, replay.settings.avoid_the_wall.min_gap);
+        assert_eq!(loaded.final_bodies, replay.final_bodies);
+
+        assert_eq!(loaded.ticks.len(), replay.ticks.len());
+        for (a, b) in loaded.ticks.iter().zip(replay.ticks.iter()) {
+            assert_eq!(a.events, b.events);
+            assert_eq!(a.guids, b.guids);
+            assert_eq!(a.states, b.states);
+        }
+    }
+
+    #[test]
+    fn load_rejects_other_versions() {
+        let path = temp_path("bad_version");
+        let mut bytes = vec![];
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(VERSION + 1);
+        File::create(&path).unwrap().write_all(&bytes).unwrap();
+        let loaded = Replay::load(&path);
+        fs::remove_file(&path).unwrap();
+
+        match loaded {
+            Err(err) => assert_eq!(err.kind(), io::ErrorKind::InvalidData),
+            Ok(_) => panic!("loaded a replay with version {}", VERSION + 1),
+        }
+    }
+
+    #[test]
+    fn load_rejects_truncated_files() {
+        let path = temp_path("truncated");
+        sample().save(&path).unwrap();
+        let mut bytes = vec![];
+        File::open(&path).unwrap().read_to_end(&mut bytes).unwrap();
+        let len = bytes.len();
+        File::create(&path).unwrap().write_all(&bytes[..len - 3]).unwrap();
+        let loaded = Replay::load(&path);
+        fs::remove_file(&path).unwrap();
+
+        assert!(loaded.is_err());
+    }
+}
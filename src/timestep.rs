@@ -0,0 +1,66 @@
+use std::time::{Duration, Instant};
+
+use config::SimulationConfig;
+
+// Accumulates real time and hands it out in fixed simulation ticks
+pub struct FixedTimestep {
+    tick: Duration,
+    max_steps: u32,
+    accumulator: Duration,
+    last: Instant,
+}
+
+impl FixedTimestep {
+    pub fn new(config: &SimulationConfig) -> FixedTimestep {
+        let tick_rate = if config.tick_rate == 0 { 60 } else { config.tick_rate };
+        // No steps at all would freeze the game
+        let max_steps = if config.max_steps_per_frame == 0 {
+            warn!("max_steps_per_frame must be at least 1, using 1");
+            1
+        } else {
+            config.max_steps_per_frame
+        };
+
+        FixedTimestep {
+            tick: Duration::new(0, 1_000_000_000u32 / tick_rate),
+            max_steps: max_steps,
+            accumulator: Duration::new(0, 0),
+            last: Instant::now(),
+        }
+    }
+
+    // Length of a tick in seconds
+    pub fn dt(&self) -> f32 {
+        return duration_secs(self.tick);
+    }
+
+    // Add the time elapsed since the last call, returns the number of ticks to simulate
+    pub fn advance(&mut self) -> u32 {
+        let now = Instant::now();
+        self.accumulator += now.duration_since(self.last);
+        self.last = now;
+
+        let mut steps = 0;
+        while self.accumulator >= self.tick && steps < self.max_steps {
+            self.accumulator -= self.tick;
+            steps = steps + 1;
+        }
+
+        // Drop the backlog instead of trying to catch up forever (spiral of death)
+        if self.accumulator >= self.tick {
+            debug!("Simulation behind by {:?}, skipping ahead", self.accumulator);
+            self.accumulator = Duration::new(0, 0);
+        }
+
+        return steps;
+    }
+
+    // How far between the previous and the current tick the rendered frame is, in [0, 1)
+    pub fn alpha(&self) -> f32 {
+        return duration_secs(self.accumulator) / duration_secs(self.tick);
+    }
+}
+
+fn duration_secs(duration: Duration) -> f32 {
+    return duration.as_secs() as f32 + duration.subsec_nanos() as f32 / 1_000_000_000.0;
+}